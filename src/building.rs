@@ -1,64 +1,227 @@
 pub mod bundle;
 pub mod components;
+pub mod depth;
+pub mod desirability;
+pub mod drag;
+pub mod farming;
+pub mod footprint;
+pub mod history;
+pub mod housing;
+pub mod industry;
+pub mod market;
+pub mod overlay;
+pub mod prices;
+pub mod risk;
+pub mod site;
+pub mod storage;
+pub mod trade;
+pub mod upkeep;
+pub mod walker;
+pub mod water;
 
+use bevy::ecs::system::SystemParam;
 use bevy::input::mouse::MouseButtonInput;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::map::TilemapGridSize;
 use bevy_ecs_tilemap::map::TilemapType;
 use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_ecs_tilemap::tiles::TileStorage;
 use bundle::BuildingMarkerBundle;
+use bundle::BUILDING_ANCHOR;
+use components::AnchorTile;
 use components::Building;
 use components::BuildingTemplateMarker;
 use components::BuildingType;
 use components::CanBuild;
+use components::Orientation;
+use depth::ZIndex;
+use depth::WALKER_LIFT;
+use drag::drag_anchors;
+use drag::BuildingDrag;
+use farming::catch_fish;
+use farming::grow_crops;
+use farming::harvest;
+use farming::sow_fields;
+use farming::update_crop_sprites;
+use footprint::CellRule;
+use history::clear_history;
+use history::BuildingAction;
+use history::BuildingState;
+use history::UndoHistory;
+use housing::feed_households;
+use housing::move_in;
+use housing::settle_households;
+use housing::Household;
+use industry::produce_materials;
+use market::supply_markets;
+use overlay::cycle_overlay;
+use overlay::tint_buildings;
+use overlay::Overlay;
+use prices::update_prices;
+use prices::MarketPrices;
+use risk::accumulate_risk;
+use risk::burn_buildings;
+use risk::OnFire;
+use risk::Risk;
+use risk::Rubble;
+use risk::RUBBLE_CLEARING_COST;
+use risk::RUBBLE_SPRITE;
+use site::progress_bar;
+use site::progress_construction;
+use site::update_progress_bars;
+use site::Finished;
+use site::UnderConstruction;
+use site::SCAFFOLDING_SPRITE;
+use storage::Storage;
+use trade::apply_trade_settings;
+use trade::leave_traders;
+use trade::visit_traders;
+use trade::TradeSettings;
+use upkeep::apply_repairs;
+use upkeep::decay_condition;
+use upkeep::pay_upkeep;
+use upkeep::Condition;
+use walker::move_walkers;
+use walker::spawn_walkers;
+use walker::WalkerRng;
+use water::update_water_network;
+use water::WaterNetworkDirty;
 
 use crate::building::bundle::BuildingBundle;
 use crate::building::components::CoveringTiles;
 
-use crate::cursor::SelectedTile;
+use crate::command::CommandSet;
+use crate::command::PlayerCommand;
+use crate::cursor::{HoveredBuilding, SelectedTile};
+use crate::grid::CurrentLevel;
 use crate::grid::Level;
+use crate::grid::MapLayout;
 use crate::grid::Occupied;
 use crate::grid::Terrain;
 use crate::grid::TILE_H;
-use crate::grid::TILE_W;
-use crate::time::GameTimer;
-use crate::time::TimeState;
+use crate::resources::collect_taxes;
+use crate::resources::GlobalResources;
+use crate::resources::Ledger;
+use crate::resources::LedgerEntry;
+use crate::time::advance_calendar;
+use crate::time::time_running;
+use crate::time::NewMonth;
 use crate::AppState;
 
 pub struct BuildingPlugin;
 impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UndoHistory>();
+        app.init_resource::<WalkerRng>();
+        app.init_resource::<TradeSettings>();
+        app.init_resource::<MarketPrices>();
+        app.init_resource::<WaterNetworkDirty>();
+
+        app.add_systems(
+            FixedUpdate,
+            (
+                (apply_building_commands, apply_repairs, apply_trade_settings)
+                    .in_set(CommandSet::Apply),
+                clear_history,
+            ),
+        );
+
+        app.add_systems(
+            FixedUpdate,
+            (
+                progress_construction,
+                decay_condition,
+                // Gold changes hands before traders work out what they can afford
+                (pay_wages, pay_upkeep, collect_taxes).chain(),
+                update_water_network,
+                accumulate_risk,
+                burn_buildings,
+                spawn_walkers,
+                move_walkers,
+                sow_fields,
+                grow_crops,
+                harvest,
+                catch_fish,
+                produce_materials,
+                move_in,
+                supply_markets,
+                feed_households,
+                settle_households,
+                update_prices,
+                visit_traders,
+                leave_traders,
+            )
+                .chain()
+                .after(CommandSet::Apply)
+                .after(advance_calendar)
+                .run_if(time_running)
+                .run_if(in_state(AppState::Level)),
+        );
+    }
+}
+
+/// Placing, demolishing and undoing buildings with the mouse and keyboard, and
+/// drawing how far along they are. Only the windowed game needs it
+pub struct BuildingInputPlugin;
+impl Plugin for BuildingInputPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<BuildingMode>();
+        app.init_resource::<BuildingDrag>();
+        app.init_resource::<Overlay>();
 
         app.add_systems(Update, enable_building.run_if(in_state(AppState::Level)));
+        app.add_systems(
+            Update,
+            demolish_building
+                .run_if(in_state(AppState::Level))
+                .run_if(resource_exists::<SelectedTile>),
+        );
 
         app.add_systems(
             Update,
             (
+                rotate_building_marker,
+                update_marker_anchors,
                 update_building_cursor,
                 check_buildable_status,
                 construct_building,
             )
+                .chain()
                 .run_if(in_state(BuildingMode::On)),
         );
 
         app.add_systems(
-            FixedUpdate,
-            (pay_wages).run_if(in_state(TimeState::Running)),
+            Update,
+            undo_redo
+                .run_if(in_state(AppState::Level))
+                .run_if(in_state(BuildingMode::Off)),
+        );
+
+        app.add_systems(
+            Update,
+            (
+                update_progress_bars,
+                update_crop_sprites,
+                tint_buildings,
+                cycle_overlay,
+            ),
         );
     }
 }
 
 fn pay_wages(
-    time: Res<Time>,
-    mut timer: ResMut<GameTimer>,
-    mut resources: ResMut<crate::resources::GlobalResources>,
-    q: Query<&BuildingType, With<Building>>,
+    mut new_month: EventReader<NewMonth>,
+    mut resources: ResMut<GlobalResources>,
+    mut ledger: ResMut<Ledger>,
+    q: Query<&BuildingType, Finished>,
 ) {
-    if timer.0.tick(time.delta()).just_finished() {
-        for building in q.iter() {
-            resources.gold -= (building.occupation() * 30) as i32;
-        }
+    for event in new_month.read() {
+        let wages: i32 = q
+            .iter()
+            .map(|building| (building.occupation() * 30) as i32)
+            .sum();
+        resources.gold -= wages;
+        ledger.book(event.month, LedgerEntry::Wages, -wages);
     }
 }
 
@@ -67,151 +230,1016 @@ fn enable_building(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     mut building_mode: ResMut<NextState<BuildingMode>>,
+    mut drag: ResMut<BuildingDrag>,
     template_q: Query<Entity, With<BuildingTemplateMarker>>,
 ) {
-    if keys.just_pressed(KeyCode::KeyB) {
-        building_mode.set(BuildingMode::On);
-
-        template_q.iter().for_each(|e| {
-            commands.entity(e).despawn();
-        });
+    // Leave shortcuts like Ctrl+Y to `undo_redo`
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
 
-        commands.spawn(BuildingMarkerBundle::theatre(asset_server));
+    let building_type = if keys.just_pressed(KeyCode::KeyB) {
+        Some(BuildingType::Theatre)
+    } else if keys.just_pressed(KeyCode::KeyH) {
+        Some(BuildingType::House)
+    } else if keys.just_pressed(KeyCode::KeyL) {
+        Some(BuildingType::Wall)
+    } else if keys.just_pressed(KeyCode::KeyP) {
+        Some(BuildingType::Prefecture)
+    } else if keys.just_pressed(KeyCode::KeyN) {
+        Some(BuildingType::EngineersPost)
+    } else if keys.just_pressed(KeyCode::KeyV) {
+        Some(BuildingType::Reservoir)
+    } else if keys.just_pressed(KeyCode::KeyC) {
+        Some(BuildingType::Aqueduct)
+    } else if keys.just_pressed(KeyCode::KeyF) {
+        Some(BuildingType::Fountain)
+    } else if keys.just_pressed(KeyCode::KeyG) {
+        Some(BuildingType::Farm)
+    } else if keys.just_pressed(KeyCode::KeyK) {
+        Some(BuildingType::Granary)
+    } else if keys.just_pressed(KeyCode::KeyT) {
+        Some(BuildingType::Orchard)
+    } else if keys.just_pressed(KeyCode::KeyU) {
+        Some(BuildingType::PigFarm)
+    } else if keys.just_pressed(KeyCode::KeyI) {
+        Some(BuildingType::Wharf)
+    } else if keys.just_pressed(KeyCode::KeyM) {
+        Some(BuildingType::Market)
+    } else if keys.just_pressed(KeyCode::KeyJ) {
+        Some(BuildingType::TradePost)
+    } else if keys.just_pressed(KeyCode::KeyY) {
+        Some(BuildingType::Dock)
+    } else if keys.just_pressed(KeyCode::Comma) {
+        Some(BuildingType::LumberCamp)
+    } else if keys.just_pressed(KeyCode::Period) {
+        Some(BuildingType::Quarry)
     } else if keys.just_pressed(KeyCode::Escape) {
-        building_mode.set(BuildingMode::Off);
+        None
+    } else {
+        return;
+    };
 
-        template_q.iter().for_each(|e| {
-            commands.entity(e).despawn();
-        });
+    template_q.iter().for_each(|e| {
+        commands.entity(e).despawn();
+    });
+    drag.0 = None;
+
+    match building_type {
+        Some(building_type) => {
+            building_mode.set(BuildingMode::On);
+            commands.spawn(BuildingMarkerBundle::build_marker(
+                building_type,
+                Orientation::default(),
+                TilePos::default(),
+                &asset_server,
+            ));
+        }
+        None => building_mode.set(BuildingMode::Off),
     }
 }
 
 // #[derive(Resource)]
 // pub struct SelectedBuilding(Option<Entity>);
 
-fn update_building_cursor(
+/**
+* Lays out one marker per building the current drag would place,
+* or a single one under the cursor when not dragging
+*/
+fn update_marker_anchors(
     selected_tile: Res<SelectedTile>,
-    tile_q: Query<&mut TilePos>,
-    tilemap_q: Query<(&TilemapType, &TilemapGridSize)>,
-    current_level: Res<crate::grid::CurrentLevel>,
-    levels: Res<Assets<Level>>,
+    drag: Res<BuildingDrag>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    tile_q: Query<&TilePos>,
     mut template_q: Query<
-        (&mut Transform, &mut Sprite, &mut CanBuild),
+        (Entity, &BuildingType, &Orientation, &mut AnchorTile),
         With<BuildingTemplateMarker>,
     >,
 ) {
-    if selected_tile.0.as_ref().is_none() || template_q.iter_mut().count() == 0 {
+    let Some(end) = selected_tile.0.and_then(|tile| tile_q.get(tile).ok()) else {
         return;
+    };
+    let Some((_, building_type, orientation, _)) = template_q.iter().next() else {
+        return;
+    };
+    let (building_type, orientation) = (*building_type, *orientation);
+
+    let mut anchors = match drag.0 {
+        Some(start) => drag_anchors(
+            building_type.drag_shape(),
+            start,
+            *end,
+            building_type.oriented_size(orientation),
+        ),
+        None => vec![*end],
     }
+    .into_iter();
 
-    let selected_tile = selected_tile.0.as_ref().unwrap();
-    let current_level = levels.get(current_level.0.id()).unwrap();
+    for (entity, _, _, mut anchor) in &mut template_q {
+        match anchors.next() {
+            Some(pos) => anchor.0 = pos,
+            None => commands.entity(entity).despawn(),
+        }
+    }
+    for pos in anchors {
+        commands.spawn(BuildingMarkerBundle::build_marker(
+            building_type,
+            orientation,
+            pos,
+            &asset_server,
+        ));
+    }
+}
 
-    for (map_type, grid_size) in tilemap_q.iter() {
-        let tile_pos = tile_q.get(*selected_tile).unwrap();
-        let tile_center = tile_pos.center_in_world(grid_size, map_type).extend(1.0);
-        for (mut transform, mut sprite, can_build) in &mut template_q {
-            transform.translation.x =
-                tile_center.x - (TILE_W * ((current_level.width / 2) - 1)) as f32;
-            transform.translation.y = tile_center.y + (TILE_H / 2) as f32;
-            transform.translation.z = 3.;
+#[allow(clippy::type_complexity)]
+fn update_building_cursor(
+    layout: Res<MapLayout>,
+    mut template_q: Query<
+        (
+            &BuildingType,
+            &Orientation,
+            &AnchorTile,
+            &mut Transform,
+            &mut Sprite,
+            &CanBuild,
+        ),
+        With<BuildingTemplateMarker>,
+    >,
+) {
+    for (building_type, orientation, anchor, mut transform, mut sprite, can_build) in
+        &mut template_q
+    {
+        let size = building_type.oriented_size(*orientation);
+        let translation = building_translation(&layout, anchor.0, size);
+        transform.translation =
+            translation.with_z(ZIndex::BuildingMarker.depth(translation.truncate()));
 
-            if can_build.0 {
-                sprite.color = BuildableColor::Green.into();
-            } else {
-                sprite.color = BuildableColor::Red.into();
-            }
+        if can_build.0 {
+            sprite.color = BuildableColor::Green.into();
+        } else {
+            sprite.color = BuildableColor::Red.into();
         }
     }
 }
 
 /**
-* Check if a position is in a region starting at `start_x`, `start_y` with width and height
+* World position of the sprite of a building `size` tiles across anchored at `anchor`,
+* standing on the front corner of its footprint
 */
-fn position_is_in_region(start: &TilePos, width: u32, height: u32, pos: &TilePos) -> bool {
-    pos.x >= start.x && pos.x < start.x + width && pos.y >= start.y && pos.y < start.y + height
+fn building_translation(layout: &MapLayout, anchor: TilePos, size: (u32, u32)) -> Vec3 {
+    let base = layout.footprint_to_world(anchor, size, BUILDING_ANCHOR, Vec2::ZERO);
+    base.extend(ZIndex::Building.depth(base))
 }
 
+/**
+* Checks every marker in placement order, so that markers can't claim the
+* same tiles twice and the gold runs out where it would when placing them
+*/
+#[allow(clippy::type_complexity)]
 fn check_buildable_status(
-    resources: Res<crate::resources::GlobalResources>,
-    selected_tile: Res<SelectedTile>,
+    resources: Res<GlobalResources>,
     mut template_q: Query<
-        (&BuildingType, &mut CanBuild, &mut CoveringTiles),
+        (
+            &BuildingType,
+            &Orientation,
+            &AnchorTile,
+            &mut CanBuild,
+            &mut CoveringTiles,
+        ),
         (With<BuildingTemplateMarker>, Without<Building>),
     >,
-    tile_q: Query<(Entity, &TilePos, &Terrain, &Occupied)>,
+    tilemap_q: Query<&TileStorage>,
+    tile_q: Query<(&Terrain, &Occupied)>,
 ) {
-    template_q
-        .iter_mut()
-        .for_each(|(building_type, mut can_build, mut possible_tiles)| {
-            if resources.gold < building_type.cost() as i32 {
-                return can_build.0 = false;
+    let Ok(tile_storage) = tilemap_q.get_single() else {
+        return;
+    };
+
+    let mut markers: Vec<_> = template_q.iter_mut().collect();
+    markers.sort_by_key(|(_, _, anchor, _, _)| (anchor.0.y, anchor.0.x));
+
+    let mut gold = resources.gold;
+    let mut claimed: Vec<Entity> = vec![];
+
+    for (building_type, orientation, anchor, mut can_build, mut possible_tiles) in markers {
+        let cost = building_type.cost() as i32;
+        if gold < cost {
+            can_build.0 = false;
+            possible_tiles.0.clear();
+            continue;
+        }
+
+        let placement = Placement {
+            building_type: *building_type,
+            anchor: anchor.0,
+            orientation: *orientation,
+        };
+
+        let covering_tiles = placement.covering_tiles(tile_storage, |tile, rule| {
+            !claimed.contains(&tile)
+                && tile_q
+                    .get(tile)
+                    .is_ok_and(|(terrain, occupied)| rule.allows(terrain) && occupied.0.is_none())
+        });
+
+        can_build.0 = covering_tiles.is_some();
+        possible_tiles.0 = covering_tiles.unwrap_or_default();
+        if can_build.0 {
+            gold -= cost;
+            claimed.extend(&possible_tiles.0);
+        }
+    }
+}
+
+fn rotate_building_marker(
+    keys: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut template_q: Query<
+        (
+            &BuildingType,
+            &mut Orientation,
+            &mut Handle<Image>,
+            &mut Sprite,
+        ),
+        With<BuildingTemplateMarker>,
+    >,
+) {
+    if !keys.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+
+    for (building_type, mut orientation, mut texture, mut sprite) in &mut template_q {
+        *orientation = orientation.rotated();
+        let (path, flip_x) = building_type.oriented_sprite(*orientation);
+        *texture = asset_server.load(path);
+        sprite.flip_x = flip_x;
+    }
+}
+
+/// A building type put at a given tile, facing a given way.
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    pub building_type: BuildingType,
+    pub anchor: TilePos,
+    pub orientation: Orientation,
+}
+
+impl Placement {
+    /**
+     * Tiles covered by the building, with what each of them has to be
+     */
+    pub fn footprint(&self) -> impl Iterator<Item = (TilePos, CellRule)> {
+        let anchor = self.anchor;
+        self.building_type
+            .footprint(self.orientation)
+            .cells
+            .iter()
+            .map(move |&(x, y, rule)| {
+                let pos = TilePos {
+                    x: anchor.x + x,
+                    y: anchor.y + y,
+                };
+                (pos, rule)
+            })
+    }
+
+    /**
+     * Tile entities under the footprint, or `None` if any of them falls off the
+     * map or is refused by `cell_allowed`
+     */
+    pub fn covering_tiles(
+        &self,
+        tile_storage: &TileStorage,
+        cell_allowed: impl Fn(Entity, CellRule) -> bool,
+    ) -> Option<Vec<Entity>> {
+        self.footprint()
+            .map(|(pos, rule)| {
+                tile_storage
+                    .checked_get(&pos)
+                    .filter(|tile| cell_allowed(*tile, rule))
+            })
+            .collect()
+    }
+}
+
+/// Everything needed to put buildings on the map and take them off again.
+#[derive(SystemParam)]
+struct Construction<'w, 's> {
+    commands: Commands<'w, 's>,
+    resources: ResMut<'w, GlobalResources>,
+    asset_server: Res<'w, AssetServer>,
+    current_level: Res<'w, CurrentLevel>,
+    levels: Res<'w, Assets<Level>>,
+    layout: Option<Res<'w, MapLayout>>,
+    tilemap_q: Query<
+        'w,
+        's,
+        (
+            &'static TilemapType,
+            &'static TilemapGridSize,
+            &'static TileStorage,
+        ),
+    >,
+    tile_q: Query<'w, 's, (&'static Terrain, &'static mut Occupied)>,
+    building_q: Query<
+        'w,
+        's,
+        (
+            &'static BuildingType,
+            &'static AnchorTile,
+            &'static Orientation,
+            Option<&'static UnderConstruction>,
+        ),
+        With<Building>,
+    >,
+    rubble_q: Query<'w, 's, (), With<Rubble>>,
+    water_dirty: ResMut<'w, WaterNetworkDirty>,
+}
+
+impl Construction<'_, '_> {
+    /**
+     * Spawns a building anchored at `anchor` if its whole footprint is free,
+     * charging its cost when `pay` is set. It starts as `site` if given,
+     * otherwise it's already finished. Returns the new building
+     */
+    fn place(
+        &mut self,
+        placement: Placement,
+        pay: bool,
+        site: Option<UnderConstruction>,
+    ) -> Option<Entity> {
+        let building_type = placement.building_type;
+        let translation = self.translation(&placement)?;
+        let (_, _, tile_storage) = self.tilemap_q.get_single().ok()?;
+
+        if pay && self.resources.gold < building_type.cost() as i32 {
+            return None;
+        }
+
+        let covering_tiles = placement.covering_tiles(tile_storage, |tile, rule| {
+            self.tile_q
+                .get(tile)
+                .is_ok_and(|(terrain, occupied)| rule.allows(terrain) && occupied.0.is_none())
+        })?;
+
+        let mut new_building = self.commands.spawn(BuildingBundle::build(
+            placement,
+            translation,
+            &self.asset_server,
+        ));
+        if let Some(site) = site {
+            new_building
+                .insert((site, self.asset_server.load::<Image>(SCAFFOLDING_SPRITE)))
+                .with_children(progress_bar);
+        }
+        let new_building_entity = new_building.id();
+
+        // Occupied is updated right away rather than through commands so that
+        // later commands in the same tick see these tiles as taken.
+        for tile in covering_tiles {
+            if let Ok((_, mut occupied)) = self.tile_q.get_mut(tile) {
+                occupied.0 = Some(new_building_entity);
             }
+        }
 
-            if let Some(selected_tile) = selected_tile.0 {
-                let (_, tile_pos, _, _) = tile_q.get(selected_tile).unwrap();
-                let (tx, ty) = building_type.size();
-
-                possible_tiles.0 = tile_q
-                    .iter()
-                    .filter(|(_, pos, terr, occupied)| {
-                        position_is_in_region(tile_pos, tx, ty, pos)
-                            && terr.is_buildable
-                            && occupied.0.is_none()
-                    })
-                    .map(|(entity, _, _, _)| entity)
-                    .collect();
-
-                can_build.0 = (possible_tiles.0.len() as u32) == tx * ty;
-            } else {
-                can_build.0 = false;
+        if pay {
+            self.resources.gold -= building_type.cost() as i32;
+        }
+        self.water_dirty.0 = true;
+
+        Some(new_building_entity)
+    }
+
+    /**
+     * Gives a building put back by undoing its demolition what it held before
+     */
+    fn restore(&mut self, building: Entity, state: &BuildingState) {
+        let mut building = self.commands.entity(building);
+        building.insert((
+            state.storage.clone(),
+            state.condition.clone(),
+            state.risk.clone(),
+        ));
+        if let Some(household) = &state.household {
+            building.insert(household.clone());
+        }
+        if let Some(on_fire) = &state.on_fire {
+            building.insert(on_fire.clone());
+        }
+    }
+
+    /**
+     * Despawns the building covering `tile` and frees its footprint,
+     * returning where it was and how far its construction got
+     */
+    fn remove(&mut self, tile: TilePos) -> Option<(Placement, Option<UnderConstruction>)> {
+        let building = self.building_at(tile)?;
+        let (building_type, anchor, orientation, site) = self.building_q.get(building).ok()?;
+        let removed = Placement {
+            building_type: *building_type,
+            anchor: anchor.0,
+            orientation: *orientation,
+        };
+        let site = site.cloned();
+
+        self.tile_q
+            .iter_mut()
+            .filter(|(_, occupied)| occupied.0 == Some(building))
+            .for_each(|(_, mut occupied)| occupied.0 = None);
+        self.commands.entity(building).despawn_recursive();
+        self.water_dirty.0 = true;
+
+        Some((removed, site))
+    }
+
+    /**
+     * Turns the finished building covering `tile` into its upgrade, charging the
+     * difference in cost. The footprint may grow over free tiles from the same
+     * anchor. It stays the same entity, so it keeps its staff, state and walkers.
+     * Returns where the building stood before
+     */
+    fn upgrade(&mut self, tile: TilePos) -> Option<Placement> {
+        let building = self.building_at(tile)?;
+        let (building_type, _, _, site) = self.building_q.get(building).ok()?;
+        let upgrade = building_type.upgrade()?;
+        if site.is_some() {
+            return None;
+        }
+        let cost = upgrade.cost() as i32 - building_type.cost() as i32;
+        if self.resources.gold < cost {
+            return None;
+        }
+
+        let previous = self.placement(building)?;
+        let placement = Placement {
+            building_type: upgrade,
+            ..previous
+        };
+        if !self.reshape(building, placement) {
+            return None;
+        }
+        self.resources.gold -= cost;
+
+        Some(previous)
+    }
+
+    /**
+     * Turns the upgraded building anchored where `previous` was back into it,
+     * refunding the difference in cost
+     */
+    fn downgrade(&mut self, previous: Placement) -> bool {
+        let Some(building) = self.building_at(previous.anchor) else {
+            return false;
+        };
+        let Some(placement) = self.placement(building) else {
+            return false;
+        };
+        if placement.anchor != previous.anchor
+            || previous.building_type.upgrade() != Some(placement.building_type)
+        {
+            return false;
+        }
+        if !self.reshape(building, previous) {
+            return false;
+        }
+        self.resources.gold +=
+            placement.building_type.cost() as i32 - previous.building_type.cost() as i32;
+
+        true
+    }
+
+    /**
+     * Turns `building` into the one at `placement` where it stands, taking the
+     * tiles of the new footprint and freeing those it no longer covers. Fails
+     * if the new footprint runs into anything else
+     */
+    fn reshape(&mut self, building: Entity, placement: Placement) -> bool {
+        let Some(translation) = self.translation(&placement) else {
+            return false;
+        };
+        let Ok((_, _, tile_storage)) = self.tilemap_q.get_single() else {
+            return false;
+        };
+        let Some(covering_tiles) = placement.covering_tiles(tile_storage, |tile, rule| {
+            self.tile_q.get(tile).is_ok_and(|(terrain, occupied)| {
+                rule.allows(terrain) && occupied.0.is_none_or(|entity| entity == building)
+            })
+        }) else {
+            return false;
+        };
+
+        self.tile_q
+            .iter_mut()
+            .filter(|(_, occupied)| occupied.0 == Some(building))
+            .for_each(|(_, mut occupied)| occupied.0 = None);
+        for tile in covering_tiles {
+            if let Ok((_, mut occupied)) = self.tile_q.get_mut(tile) {
+                occupied.0 = Some(building);
             }
-        });
+        }
+        self.water_dirty.0 = true;
+
+        // The sprite keeps its anchor, it only moves to fit the new footprint
+        let (sprite, flip_x) = placement
+            .building_type
+            .oriented_sprite(placement.orientation);
+        self.commands
+            .entity(building)
+            .insert((
+                placement.building_type,
+                self.asset_server.load::<Image>(sprite),
+            ))
+            .add(move |mut entity: EntityWorldMut| {
+                if let Some(mut sprite) = entity.get_mut::<Sprite>() {
+                    sprite.flip_x = flip_x;
+                }
+                if let Some(mut transform) = entity.get_mut::<Transform>() {
+                    transform.translation = translation;
+                }
+            });
+
+        true
+    }
+
+    /**
+     * The building covering `tile`, if any
+     */
+    fn building_at(&self, tile: TilePos) -> Option<Entity> {
+        let (_, _, tile_storage) = self.tilemap_q.get_single().ok()?;
+        let tile = tile_storage.checked_get(&tile)?;
+        let building = self.tile_q.get(tile).ok()?.1 .0?;
+        self.building_q.contains(building).then_some(building)
+    }
+
+    /**
+     * Where `building` stands, if it's a building
+     */
+    fn placement(&self, building: Entity) -> Option<Placement> {
+        let (building_type, anchor, orientation, _) = self.building_q.get(building).ok()?;
+        Some(Placement {
+            building_type: *building_type,
+            anchor: anchor.0,
+            orientation: *orientation,
+        })
+    }
+
+    /**
+     * Tiles covered by `building`
+     */
+    fn area(&self, building: Entity) -> Vec<TilePos> {
+        self.placement(building)
+            .map(|placement| placement.footprint().map(|(pos, _)| pos).collect())
+            .unwrap_or_default()
+    }
+
+    /**
+     * Buildings sharing a side with any tile of `area`, each listed once
+     */
+    fn buildings_next_to(&self, area: &[TilePos]) -> Vec<Entity> {
+        let mut buildings = vec![];
+        for pos in area {
+            for next in self.neighbours(*pos) {
+                let building = self.building_at(next);
+                if let Some(building) = building.filter(|building| !buildings.contains(building)) {
+                    buildings.push(building);
+                }
+            }
+        }
+        buildings
+    }
+
+    /**
+     * Buildings covering any tile within `radius` tiles of `area`, each listed once
+     */
+    fn buildings_around(&self, area: &[TilePos], radius: u32) -> Vec<Entity> {
+        let mut buildings = vec![];
+        let Ok((_, _, tile_storage)) = self.tilemap_q.get_single() else {
+            return buildings;
+        };
+
+        for pos in area {
+            for x in pos.x.saturating_sub(radius)..=pos.x + radius {
+                for y in pos.y.saturating_sub(radius)..=pos.y + radius {
+                    let building = tile_storage
+                        .checked_get(&TilePos { x, y })
+                        .and_then(|tile| self.tile_q.get(tile).ok())
+                        .and_then(|(_, occupied)| occupied.0)
+                        .filter(|building| self.building_q.contains(*building));
+                    if let Some(building) = building {
+                        if !buildings.contains(&building) {
+                            buildings.push(building);
+                        }
+                    }
+                }
+            }
+        }
+
+        buildings
+    }
+
+    /**
+     * Tiles sharing a side with `pos`, as long as they're on the map
+     */
+    fn neighbours(&self, pos: TilePos) -> Vec<TilePos> {
+        let Ok((_, _, tile_storage)) = self.tilemap_q.get_single() else {
+            return vec![];
+        };
+        let neighbours = [
+            (pos.x.checked_sub(1), Some(pos.y)),
+            (Some(pos.x + 1), Some(pos.y)),
+            (Some(pos.x), pos.y.checked_sub(1)),
+            (Some(pos.x), Some(pos.y + 1)),
+        ];
+
+        neighbours
+            .into_iter()
+            .filter_map(|(x, y)| Some(TilePos { x: x?, y: y? }))
+            .filter(|pos| pos.within_map_bounds(&tile_storage.size))
+            .collect()
+    }
+
+    /**
+     * Land tiles next to `pos` that walkers can step on
+     */
+    fn walkable_neighbours(&self, pos: TilePos) -> Vec<TilePos> {
+        let Ok((_, _, tile_storage)) = self.tilemap_q.get_single() else {
+            return vec![];
+        };
+
+        self.neighbours(pos)
+            .into_iter()
+            .filter(|pos| {
+                tile_storage
+                    .checked_get(pos)
+                    .and_then(|tile| self.tile_q.get(tile).ok())
+                    .is_some_and(|(terrain, _)| terrain.is_buildable)
+            })
+            .collect()
+    }
+
+    /**
+     * World position of the sprite of a building at `placement`
+     */
+    fn translation(&self, placement: &Placement) -> Option<Vec3> {
+        let layout = self.layout.as_ref()?;
+        let size = placement.building_type.oriented_size(placement.orientation);
+        Some(building_translation(layout, placement.anchor, size))
+    }
+
+    /**
+     * World position of the centre of the tile at `pos`, in front of the buildings there
+     */
+    fn tile_translation(&self, pos: TilePos) -> Option<Vec3> {
+        let centre = self.layout.as_ref()?.tile_to_world(pos);
+        Some(centre.extend(ZIndex::Building.depth(centre)))
+    }
+
+    fn terrain(&self, pos: TilePos) -> Option<&Terrain> {
+        let (_, _, tile_storage) = self.tilemap_q.get_single().ok()?;
+        let tile = tile_storage.checked_get(&pos)?;
+        self.tile_q.get(tile).ok().map(|(terrain, _)| terrain)
+    }
+
+    /**
+     * World position of a walker standing on the centre of `pos`, drawn in front of a building there
+     */
+    fn walker_translation(&self, pos: TilePos) -> Option<Vec3> {
+        let translation = self.tile_translation(pos)?;
+        Some(
+            translation
+                .with_y(translation.y + (TILE_H / 4) as f32)
+                .with_z(translation.z + WALKER_LIFT),
+        )
+    }
+
+    /**
+     * Replaces `building` with rubble on every tile it covered
+     */
+    fn collapse(&mut self, building: Entity) {
+        let Some(placement) = self.placement(building) else {
+            return;
+        };
+        if self.remove(placement.anchor).is_none() {
+            return;
+        }
+        let Some(layout) = self.layout.as_deref().copied() else {
+            return;
+        };
+        let Ok((_, _, tile_storage)) = self.tilemap_q.get_single() else {
+            return;
+        };
+
+        for (pos, _) in placement.footprint() {
+            let Some(tile) = tile_storage.checked_get(&pos) else {
+                continue;
+            };
+            let rubble = self
+                .commands
+                .spawn((
+                    Rubble,
+                    AnchorTile(pos),
+                    SpriteBundle {
+                        sprite: Sprite {
+                            anchor: BUILDING_ANCHOR,
+                            ..default()
+                        },
+                        texture: self.asset_server.load(RUBBLE_SPRITE),
+                        transform: Transform::from_translation(building_translation(
+                            &layout,
+                            pos,
+                            (1, 1),
+                        )),
+                        ..default()
+                    },
+                ))
+                .id();
+            if let Ok((_, mut occupied)) = self.tile_q.get_mut(tile) {
+                occupied.0 = Some(rubble);
+            }
+        }
+    }
+
+    /**
+     * Clears the rubble on `tile`, if there's any and it can be paid for
+     */
+    fn clear_rubble(&mut self, tile: TilePos) -> bool {
+        let Ok((_, _, tile_storage)) = self.tilemap_q.get_single() else {
+            return false;
+        };
+        let Some(tile) = tile_storage.checked_get(&tile) else {
+            return false;
+        };
+        let Ok((_, mut occupied)) = self.tile_q.get_mut(tile) else {
+            return false;
+        };
+        let Some(rubble) = occupied.0.filter(|entity| self.rubble_q.contains(*entity)) else {
+            return false;
+        };
+        if self.resources.gold < RUBBLE_CLEARING_COST as i32 {
+            return false;
+        }
+
+        occupied.0 = None;
+        self.commands.entity(rubble).despawn();
+        self.resources.gold -= RUBBLE_CLEARING_COST as i32;
+        true
+    }
+}
+
+/// What a building holds, kept when it's demolished so that can be undone
+type HeldState<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static UnderConstruction>,
+        &'static Storage,
+        &'static Condition,
+        &'static Risk,
+        Option<&'static Household>,
+        Option<&'static OnFire>,
+    ),
+    With<Building>,
+>;
+
+/**
+* Demolishes the building covering `tile`, returning where it stood and what it held
+*/
+fn demolish(
+    construction: &mut Construction,
+    state_q: &HeldState,
+    tile: TilePos,
+) -> Option<(Placement, BuildingState)> {
+    let building = construction.building_at(tile)?;
+    let (site, storage, condition, risk, household, on_fire) = state_q.get(building).ok()?;
+    let state = BuildingState {
+        site: site.cloned(),
+        storage: storage.clone(),
+        condition: condition.clone(),
+        risk: risk.clone(),
+        household: household.cloned(),
+        on_fire: on_fire.cloned(),
+    };
+    let (placement, _) = construction.remove(tile)?;
+    Some((placement, state))
+}
+
+fn apply_building_commands(
+    mut player_commands: EventReader<PlayerCommand>,
+    mut construction: Construction,
+    mut history: ResMut<UndoHistory>,
+    state_q: HeldState,
+) {
+    for player_command in player_commands.read() {
+        match *player_command {
+            PlayerCommand::PlaceBuilding {
+                building_type,
+                x,
+                y,
+                orientation,
+            } => {
+                let placement = Placement {
+                    building_type,
+                    anchor: TilePos { x, y },
+                    orientation,
+                };
+                if construction
+                    .place(placement, true, Some(UnderConstruction::default()))
+                    .is_some()
+                {
+                    history.record(BuildingAction::Placed(placement));
+                } else {
+                    warn!("Cannot apply {:?}", player_command);
+                }
+            }
+            PlayerCommand::Demolish { x, y } => {
+                match demolish(&mut construction, &state_q, TilePos { x, y }) {
+                    Some((placement, state)) => {
+                        history.record(BuildingAction::Demolished(placement, state));
+                    }
+                    None if construction.clear_rubble(TilePos { x, y }) => {}
+                    None => warn!("Cannot apply {:?}: no building there", player_command),
+                }
+            }
+            PlayerCommand::Upgrade { x, y } => match construction.upgrade(TilePos { x, y }) {
+                Some(previous) => history.record(BuildingAction::Upgraded(previous)),
+                None => warn!("Cannot apply {:?}", player_command),
+            },
+            PlayerCommand::Undo => {
+                let Some(action) = history.undo.pop() else {
+                    continue;
+                };
+                let undone = match &action {
+                    BuildingAction::Placed(placement) => construction
+                        .remove(placement.anchor)
+                        .is_some_and(|(_, site)| {
+                            let resources = &mut construction.resources;
+                            resources.gold += placement.building_type.cost() as i32;
+                            // A finished building used up all of its materials
+                            let delivered = match site {
+                                Some(site) => site.delivered,
+                                None => placement
+                                    .building_type
+                                    .materials()
+                                    .iter()
+                                    .copied()
+                                    .collect(),
+                            };
+                            for (good, delivered) in delivered {
+                                *resources.goods.entry(good).or_insert(0) += delivered;
+                            }
+                            true
+                        }),
+                    BuildingAction::Demolished(placement, state) => construction
+                        .place(*placement, false, state.site.clone())
+                        .map(|building| construction.restore(building, state))
+                        .is_some(),
+                    BuildingAction::Upgraded(previous) => construction.downgrade(*previous),
+                };
+                if undone {
+                    history.redo.push(action);
+                } else {
+                    warn!("Cannot undo {:?}", action);
+                }
+            }
+            PlayerCommand::Redo => {
+                let Some(mut action) = history.redo.pop() else {
+                    continue;
+                };
+                let redone = match &mut action {
+                    BuildingAction::Placed(placement) => construction
+                        .place(*placement, true, Some(UnderConstruction::default()))
+                        .is_some(),
+                    // Whatever the building gained since is kept for the next undo
+                    BuildingAction::Demolished(placement, state) => {
+                        match demolish(&mut construction, &state_q, placement.anchor) {
+                            Some((_, held)) => {
+                                *state = held;
+                                true
+                            }
+                            None => false,
+                        }
+                    }
+                    BuildingAction::Upgraded(previous) => {
+                        construction.upgrade(previous.anchor).is_some()
+                    }
+                };
+                if redone {
+                    history.undo.push(action);
+                } else {
+                    warn!("Cannot redo {:?}", action);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
+/**
+* Pressing the mouse starts a drag, releasing it places every valid marker.
+* Building mode stays on afterwards while shift is held
+*/
+#[allow(clippy::too_many_arguments)]
 fn construct_building(
     mut mouse: EventReader<MouseButtonInput>,
+    keys: Res<ButtonInput<KeyCode>>,
     mut commands: Commands,
-    mut resources: ResMut<crate::resources::GlobalResources>,
+    mut player_commands: EventWriter<PlayerCommand>,
     mut building_mode: ResMut<NextState<BuildingMode>>,
-    asset_server: Res<AssetServer>,
+    mut drag: ResMut<BuildingDrag>,
+    selected_tile: Res<SelectedTile>,
+    tile_q: Query<&TilePos>,
     marker_entity_q: Query<Entity, With<BuildingTemplateMarker>>,
     marker_components_q: Query<
-        (&BuildingType, &Transform, &CoveringTiles, &CanBuild),
+        (&BuildingType, &Orientation, &AnchorTile, &CanBuild),
         With<BuildingTemplateMarker>,
     >,
 ) {
-    mouse.read().for_each(|event| {
-        if event.button == MouseButton::Left && event.state.is_pressed() {
-            marker_components_q.iter().for_each(
-                |(building_type, transform, covering_tiles, can_build)| {
-                    if can_build.0 {
-                        let new_building_entity = commands
-                            .spawn(BuildingBundle::build(
-                                *building_type,
-                                transform.translation,
-                                &asset_server,
-                            ))
-                            .id();
-
-                        resources.gold -= building_type.cost() as i32;
-                        building_mode.set(BuildingMode::Off);
-
-                        covering_tiles.0.iter().for_each(|e| {
-                            commands
-                                .entity(*e)
-                                .remove::<Occupied>()
-                                .insert(Occupied(Some(new_building_entity)));
-                        });
-
-                        marker_entity_q.iter().for_each(|e| {
-                            commands.entity(e).despawn();
-                        });
-                    }
-                },
-            );
+    for event in mouse.read() {
+        if event.button != MouseButton::Left {
+            continue;
         }
-    });
+
+        if event.state.is_pressed() {
+            drag.0 = selected_tile
+                .0
+                .and_then(|tile| tile_q.get(tile).ok())
+                .copied();
+            continue;
+        }
+
+        if drag.0.take().is_none() {
+            continue;
+        }
+
+        let mut placements: Vec<_> = marker_components_q
+            .iter()
+            .filter(|(_, _, _, can_build)| can_build.0)
+            .collect();
+        placements.sort_by_key(|(_, _, anchor, _)| (anchor.0.y, anchor.0.x));
+
+        for (building_type, orientation, anchor, _) in &placements {
+            player_commands.send(PlayerCommand::PlaceBuilding {
+                building_type: **building_type,
+                x: anchor.0.x,
+                y: anchor.0.y,
+                orientation: **orientation,
+            });
+        }
+
+        if !placements.is_empty() && !keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            building_mode.set(BuildingMode::Off);
+
+            marker_entity_q.iter().for_each(|e| {
+                commands.entity(e).despawn();
+            });
+        }
+    }
+}
+
+/**
+* Demolishes the building drawn under the cursor with Delete, or else
+* whatever occupies the tile there
+*/
+fn demolish_building(
+    keys: Res<ButtonInput<KeyCode>>,
+    selected_tile: Res<SelectedTile>,
+    hovered_building: Res<HoveredBuilding>,
+    tile_q: Query<(&TilePos, &Occupied)>,
+    anchor_q: Query<&AnchorTile>,
+    mut player_commands: EventWriter<PlayerCommand>,
+) {
+    if !keys.just_pressed(KeyCode::Delete) {
+        return;
+    }
+
+    let building = hovered_building
+        .0
+        .and_then(|building| anchor_q.get(building).ok())
+        .map(|anchor| anchor.0);
+    let occupied = selected_tile
+        .0
+        .and_then(|t| tile_q.get(t).ok())
+        .filter(|(_, occupied)| occupied.0.is_some())
+        .map(|(tile_pos, _)| *tile_pos);
+    if let Some(tile_pos) = building.or(occupied) {
+        player_commands.send(PlayerCommand::Demolish {
+            x: tile_pos.x,
+            y: tile_pos.y,
+        });
+    }
+}
+
+fn undo_redo(keys: Res<ButtonInput<KeyCode>>, mut player_commands: EventWriter<PlayerCommand>) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::KeyZ) {
+        player_commands.send(PlayerCommand::Undo);
+    } else if keys.just_pressed(KeyCode::KeyY) {
+        player_commands.send(PlayerCommand::Redo);
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -236,3 +1264,102 @@ impl From<BuildableColor> for Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use super::*;
+    use crate::resources::Good;
+
+    const MAP_SIZE: TilemapSize = TilemapSize { x: 6, y: 6 };
+
+    /// A grass map with nothing built on it yet
+    fn level() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            ImagePlugin::default(),
+        ))
+        .init_asset::<Level>()
+        .add_event::<PlayerCommand>()
+        .insert_resource(CurrentLevel(Handle::default()))
+        .insert_resource(MapLayout::new(MAP_SIZE))
+        .init_resource::<GlobalResources>()
+        .init_resource::<UndoHistory>()
+        .init_resource::<WaterNetworkDirty>();
+
+        let world = app.world_mut();
+        let mut tile_storage = TileStorage::empty(MAP_SIZE);
+        for x in 0..MAP_SIZE.x {
+            for y in 0..MAP_SIZE.y {
+                let pos = TilePos { x, y };
+                let tile = world.spawn((pos, Terrain::default(), Occupied(None))).id();
+                tile_storage.set(&pos, tile);
+            }
+        }
+        world.spawn((
+            TilemapType::Isometric(IsoCoordSystem::Diamond),
+            TilemapGridSize { x: 64., y: 32. },
+            tile_storage,
+        ));
+        app
+    }
+
+    /// Applies `command` the way the simulation does at the next step
+    fn apply(app: &mut App, command: PlayerCommand) {
+        let world = app.world_mut();
+        world.send_event(command);
+        world.run_system_once(apply_building_commands);
+        world.resource_mut::<Events<PlayerCommand>>().clear();
+    }
+
+    #[test]
+    fn undoing_a_demolition_gives_back_what_the_building_held() {
+        let mut app = level();
+        let placement = Placement {
+            building_type: BuildingType::Granary,
+            anchor: TilePos { x: 2, y: 2 },
+            orientation: Orientation::North,
+        };
+        let granary = app
+            .world_mut()
+            .run_system_once(move |mut construction: Construction| {
+                construction.place(placement, false, None)
+            })
+            .expect("the granary fits on the map");
+        app.world_mut().entity_mut(granary).insert((
+            Storage {
+                goods: [(Good::Wheat, 80), (Good::Fish, 30)].into(),
+            },
+            Condition(0.6),
+            Risk {
+                fire: 0.2,
+                damage: 0.4,
+            },
+        ));
+
+        apply(&mut app, PlayerCommand::Demolish { x: 3, y: 3 });
+        let mut granaries = app.world_mut().query_filtered::<Entity, With<Building>>();
+        assert_eq!(granaries.iter(app.world()).count(), 0);
+
+        apply(&mut app, PlayerCommand::Undo);
+        let mut granaries = app.world_mut().query::<(
+            &BuildingType,
+            &AnchorTile,
+            &Storage,
+            &Condition,
+            &Risk,
+            Has<UnderConstruction>,
+        )>();
+        let (building_type, anchor, storage, condition, risk, site) = granaries.single(app.world());
+        assert_eq!(*building_type, BuildingType::Granary);
+        assert_eq!(anchor.0, placement.anchor);
+        assert_eq!(storage.goods, [(Good::Wheat, 80), (Good::Fish, 30)].into());
+        assert_eq!(condition.0, 0.6);
+        assert_eq!((risk.fire, risk.damage), (0.2, 0.4));
+        assert!(!site);
+    }
+}
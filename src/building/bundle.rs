@@ -1,30 +1,55 @@
 use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy_ecs_tilemap::tiles::TilePos;
 
 use super::{
-    components::{Building, BuildingTemplateMarker, BuildingType, CanBuild, CoveringTiles},
-    BuildableColor,
+    components::{
+        AnchorTile, Building, BuildingTemplateMarker, BuildingType, CanBuild, CoveringTiles,
+        Orientation,
+    },
+    risk::Risk,
+    storage::Storage,
+    upkeep::Condition,
+    BuildableColor, Placement,
 };
 
+/// Where building sprites are drawn from, see `MapLayout::footprint_to_world`
+pub const BUILDING_ANCHOR: Anchor = Anchor::BottomCenter;
+
 #[derive(Bundle)]
 pub struct BuildingBundle {
     pub building: Building,
     pub building_type: BuildingType,
+    pub anchor: AnchorTile,
+    pub orientation: Orientation,
+    pub risk: Risk,
+    pub condition: Condition,
+    pub storage: Storage,
     pub sprite: SpriteBundle,
 }
 
 impl BuildingBundle {
-    pub fn build(
-        building_type: BuildingType,
-        position: Vec3,
-        asset_server: &Res<AssetServer>,
-    ) -> Self {
+    pub fn build(placement: Placement, position: Vec3, asset_server: &Res<AssetServer>) -> Self {
         let transform = Transform::from_xyz(position.x, position.y, position.z);
-        let texture = asset_server.load(building_type.sprite());
+        let (sprite, flip_x) = placement
+            .building_type
+            .oriented_sprite(placement.orientation);
+        let texture = asset_server.load(sprite);
 
         Self {
             building: Building,
-            building_type,
+            building_type: placement.building_type,
+            anchor: AnchorTile(placement.anchor),
+            orientation: placement.orientation,
+            risk: Risk::default(),
+            condition: Condition::default(),
+            storage: Storage::default(),
             sprite: SpriteBundle {
+                sprite: Sprite {
+                    flip_x,
+                    anchor: BUILDING_ANCHOR,
+                    ..default()
+                },
                 texture,
                 transform,
                 ..Default::default()
@@ -38,32 +63,39 @@ pub struct BuildingMarkerBundle {
     marker: BuildingTemplateMarker,
     can_build: CanBuild,
     marker_type: BuildingType,
+    orientation: Orientation,
+    anchor: AnchorTile,
     covering_tiles: CoveringTiles,
     sprite: SpriteBundle,
 }
 
 impl BuildingMarkerBundle {
-    pub fn build_marker(marker_type: BuildingType, sprite_texture: Handle<Image>) -> Self {
+    pub fn build_marker(
+        marker_type: BuildingType,
+        orientation: Orientation,
+        anchor: TilePos,
+        asset_server: &AssetServer,
+    ) -> Self {
+        let (sprite, flip_x) = marker_type.oriented_sprite(orientation);
+
         BuildingMarkerBundle {
             marker: BuildingTemplateMarker,
             marker_type,
+            orientation,
+            anchor: AnchorTile(anchor),
             can_build: CanBuild(false),
             covering_tiles: CoveringTiles(vec![]),
             sprite: SpriteBundle {
                 sprite: Sprite {
                     color: BuildableColor::default().into(),
+                    flip_x,
+                    anchor: BUILDING_ANCHOR,
                     ..default()
                 },
-                texture: sprite_texture,
+                texture: asset_server.load(sprite),
                 transform: Transform::from_xyz(100000000., 100000000., 2.),
                 ..Default::default()
             },
         }
     }
-    pub fn theatre(asset_server: Res<AssetServer>) -> Self {
-        Self::build_marker(
-            BuildingType::Theatre,
-            asset_server.load(BuildingType::Theatre.sprite()).clone(),
-        )
-    }
 }
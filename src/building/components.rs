@@ -1,29 +1,233 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
 use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use super::drag::DragShape;
+use super::footprint::Footprint;
+use super::walker::WalkerKind;
+use crate::resources::Good;
 
 #[derive(Component)]
 pub struct Building;
 
+/// The tile a building was placed at, i.e. the corner of its footprint,
+/// or the tile a heap of rubble lies on
 #[derive(Component, Clone, Copy)]
+pub struct AnchorTile(pub TilePos);
+
+#[derive(
+    Component, Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub enum BuildingType {
     Theatre,
+    Amphitheatre,
+    House,
+    Wall,
+    Prefecture,
+    EngineersPost,
+    Reservoir,
+    Aqueduct,
+    Fountain,
+    Farm,
+    Granary,
+    Orchard,
+    PigFarm,
+    Wharf,
+    Market,
+    TradePost,
+    Dock,
+    LumberCamp,
+    Quarry,
 }
 
 impl BuildingType {
-    pub fn size(&self) -> (u32, u32) {
+    pub const ALL: [BuildingType; 19] = [
+        BuildingType::Theatre,
+        BuildingType::Amphitheatre,
+        BuildingType::House,
+        BuildingType::Wall,
+        BuildingType::Prefecture,
+        BuildingType::EngineersPost,
+        BuildingType::Reservoir,
+        BuildingType::Aqueduct,
+        BuildingType::Fountain,
+        BuildingType::Farm,
+        BuildingType::Granary,
+        BuildingType::Orchard,
+        BuildingType::PigFarm,
+        BuildingType::Wharf,
+        BuildingType::Market,
+        BuildingType::TradePost,
+        BuildingType::Dock,
+        BuildingType::LumberCamp,
+        BuildingType::Quarry,
+    ];
+
+    /// See `Footprint::parse` for the mask format
+    pub fn footprint_mask(&self) -> &'static str {
         match self {
-            BuildingType::Theatre => (2, 2).to_owned(),
+            BuildingType::Theatre => "LL\nLL",
+            BuildingType::Amphitheatre
+            | BuildingType::Farm
+            | BuildingType::Orchard
+            | BuildingType::PigFarm => "LLL\nLLL\nLLL",
+            BuildingType::Granary | BuildingType::TradePost => "LL\nLL",
+            // Stalls around a corner, leaving the far tile free
+            BuildingType::Market => "LL\nL.",
+            BuildingType::House
+            | BuildingType::Wall
+            | BuildingType::Prefecture
+            | BuildingType::EngineersPost
+            | BuildingType::Aqueduct
+            | BuildingType::Fountain
+            | BuildingType::LumberCamp
+            | BuildingType::Quarry => "L",
+            // Draws from the water along one side
+            BuildingType::Reservoir => "LLW\nLLW",
+            // Moored on the shore
+            BuildingType::Wharf => "LW",
+            BuildingType::Dock => "LW\nLW",
         }
     }
 
+    /// Footprint turned to `orientation`. Masks are parsed and turned once,
+    /// the first time any footprint is asked for
+    pub fn footprint(&self, orientation: Orientation) -> &'static Footprint {
+        static FOOTPRINTS: OnceLock<HashMap<(BuildingType, Orientation), Footprint>> =
+            OnceLock::new();
+        let footprints = FOOTPRINTS.get_or_init(|| {
+            BuildingType::ALL
+                .iter()
+                .flat_map(|building_type| {
+                    let footprint = Footprint::parse(building_type.footprint_mask());
+                    Orientation::ALL.map(|orientation| {
+                        (
+                            (*building_type, orientation),
+                            footprint.oriented(orientation),
+                        )
+                    })
+                })
+                .collect()
+        });
+        &footprints[&(*self, orientation)]
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.oriented_size(Orientation::North)
+    }
+
+    /// Footprint size once rotated to `orientation`
+    pub fn oriented_size(&self, orientation: Orientation) -> (u32, u32) {
+        let footprint = self.footprint(orientation);
+        (footprint.width, footprint.height)
+    }
+
+    /// Drawing of the building facing North
     pub fn sprite(&self) -> &'static str {
         match self {
             BuildingType::Theatre => "buildings/theatre.png",
+            BuildingType::Amphitheatre => "buildings/amphitheatre.png",
+            BuildingType::House => "buildings/house.png",
+            BuildingType::Wall => "buildings/wall.png",
+            BuildingType::Prefecture => "buildings/prefecture.png",
+            BuildingType::EngineersPost => "buildings/engineers_post.png",
+            BuildingType::Reservoir => "buildings/reservoir_north.png",
+            BuildingType::Aqueduct => "buildings/aqueduct.png",
+            BuildingType::Fountain => "buildings/fountain.png",
+            BuildingType::Farm => "buildings/farm.png",
+            BuildingType::Granary => "buildings/granary.png",
+            BuildingType::Orchard => "buildings/orchard.png",
+            BuildingType::PigFarm => "buildings/pig_farm.png",
+            BuildingType::Wharf => "buildings/wharf_north.png",
+            BuildingType::Market => "buildings/market_north.png",
+            BuildingType::TradePost => "buildings/trade_post.png",
+            BuildingType::Dock => "buildings/dock_north.png",
+            BuildingType::LumberCamp => "buildings/lumber_camp.png",
+            BuildingType::Quarry => "buildings/quarry.png",
         }
     }
+
+    /// A drawing for each way the building faces, in the order of
+    /// `Orientation::ALL`, for buildings whose footprint isn't symmetric
+    fn directional_sprites(&self) -> Option<[&'static str; 4]> {
+        let sprites = match self {
+            BuildingType::Reservoir => [
+                "buildings/reservoir_north.png",
+                "buildings/reservoir_east.png",
+                "buildings/reservoir_south.png",
+                "buildings/reservoir_west.png",
+            ],
+            BuildingType::Wharf => [
+                "buildings/wharf_north.png",
+                "buildings/wharf_east.png",
+                "buildings/wharf_south.png",
+                "buildings/wharf_west.png",
+            ],
+            BuildingType::Market => [
+                "buildings/market_north.png",
+                "buildings/market_east.png",
+                "buildings/market_south.png",
+                "buildings/market_west.png",
+            ],
+            BuildingType::Dock => [
+                "buildings/dock_north.png",
+                "buildings/dock_east.png",
+                "buildings/dock_south.png",
+                "buildings/dock_west.png",
+            ],
+            _ => return None,
+        };
+        Some(sprites)
+    }
+
+    /// Whether the sprite has a drawing for each way the building faces
+    pub fn is_directional(&self) -> bool {
+        self.directional_sprites().is_some()
+    }
+
+    /// Whether East and West show the sprite mirrored, for buildings with a
+    /// symmetric footprint whose drawing isn't symmetric
+    fn mirrors(&self) -> bool {
+        matches!(
+            self,
+            BuildingType::Theatre
+                | BuildingType::Amphitheatre
+                | BuildingType::House
+                | BuildingType::Farm
+                | BuildingType::Granary
+                | BuildingType::Orchard
+                | BuildingType::PigFarm
+        )
+    }
+
+    /// Sprite for `orientation` and whether it must be mirrored horizontally
+    pub fn oriented_sprite(&self, orientation: Orientation) -> (&'static str, bool) {
+        if let Some(sprites) = self.directional_sprites() {
+            return (sprites[orientation as usize], false);
+        }
+        let mirrored =
+            self.mirrors() && matches!(orientation, Orientation::East | Orientation::West);
+        (self.sprite(), mirrored)
+    }
+
     pub fn occupation(&self) -> u32 {
         match self {
             BuildingType::Theatre => 4,
-            //            BuildingType::Amphiteatre => 8,
+            BuildingType::Amphitheatre => 8,
+            BuildingType::House | BuildingType::Wall => 0,
+            BuildingType::Prefecture | BuildingType::EngineersPost => 2,
+            BuildingType::Reservoir => 2,
+            BuildingType::Aqueduct => 0,
+            BuildingType::Fountain => 1,
+            BuildingType::Farm => 3,
+            BuildingType::Granary => 2,
+            BuildingType::Orchard | BuildingType::PigFarm => 3,
+            BuildingType::Wharf => 2,
+            BuildingType::Market => 3,
+            BuildingType::TradePost | BuildingType::Dock => 3,
+            BuildingType::LumberCamp | BuildingType::Quarry => 3,
             //            BuildingType::Colosseum => 12,
         }
     }
@@ -31,18 +235,352 @@ impl BuildingType {
     pub fn cost(&self) -> u32 {
         match self {
             BuildingType::Theatre => 10,
-            //            BuildingType::Amphiteatre => 20,
+            BuildingType::House => 5,
+            BuildingType::Wall => 2,
+            BuildingType::Prefecture | BuildingType::EngineersPost => 15,
+            BuildingType::Reservoir => 30,
+            BuildingType::Aqueduct => 3,
+            BuildingType::Fountain => 8,
+            BuildingType::Farm => 20,
+            BuildingType::Granary => 25,
+            BuildingType::Amphitheatre => 20,
+            BuildingType::Orchard => 20,
+            BuildingType::PigFarm => 25,
+            BuildingType::Wharf => 15,
+            BuildingType::Market => 20,
+            BuildingType::TradePost => 30,
+            BuildingType::Dock => 35,
+            BuildingType::LumberCamp => 20,
+            BuildingType::Quarry => 25,
             //            BuildingType::Colosseum => 30,
         }
     }
 
+    /// In-game months it takes to build, once all materials are delivered
+    pub fn build_months(&self) -> f32 {
+        match self {
+            BuildingType::Theatre => 2.,
+            BuildingType::Amphitheatre => 3.,
+            BuildingType::House => 0.5,
+            BuildingType::Wall => 0.25,
+            BuildingType::Prefecture | BuildingType::EngineersPost => 0.5,
+            BuildingType::Reservoir => 1.,
+            BuildingType::Aqueduct => 0.25,
+            BuildingType::Fountain => 0.5,
+            BuildingType::Farm | BuildingType::Granary => 1.,
+            BuildingType::Orchard | BuildingType::PigFarm => 1.,
+            BuildingType::Wharf => 0.5,
+            BuildingType::Market => 1.,
+            BuildingType::TradePost | BuildingType::Dock => 1.,
+            BuildingType::LumberCamp | BuildingType::Quarry => 0.5,
+        }
+    }
+
+    /// Materials that have to be delivered to the construction site
+    pub fn materials(&self) -> &'static [(Good, u32)] {
+        match self {
+            BuildingType::Theatre => &[(Good::Timber, 4), (Good::Stone, 6)],
+            BuildingType::Amphitheatre => &[(Good::Timber, 8), (Good::Stone, 12)],
+            BuildingType::House => &[(Good::Timber, 1)],
+            BuildingType::Wall => &[(Good::Stone, 1)],
+            BuildingType::Prefecture | BuildingType::EngineersPost => {
+                &[(Good::Timber, 2), (Good::Stone, 2)]
+            }
+            BuildingType::Reservoir => &[(Good::Timber, 2), (Good::Stone, 8)],
+            BuildingType::Aqueduct => &[(Good::Stone, 1)],
+            BuildingType::Fountain => &[(Good::Stone, 2)],
+            BuildingType::Farm => &[(Good::Timber, 4)],
+            BuildingType::Granary => &[(Good::Timber, 6), (Good::Stone, 2)],
+            BuildingType::Orchard | BuildingType::PigFarm => &[(Good::Timber, 4)],
+            BuildingType::Wharf => &[(Good::Timber, 3)],
+            BuildingType::Market => &[(Good::Timber, 3), (Good::Stone, 2)],
+            BuildingType::TradePost => &[(Good::Timber, 4), (Good::Stone, 4)],
+            BuildingType::Dock => &[(Good::Timber, 6), (Good::Stone, 2)],
+            // Built from what's at hand, so that materials can always be had
+            BuildingType::LumberCamp | BuildingType::Quarry => &[],
+        }
+    }
+
+    /// Fire risk gained per month, a fire breaks out when it reaches 1
+    pub fn fire_risk(&self) -> f32 {
+        match self {
+            BuildingType::Theatre => 0.15,
+            BuildingType::Amphitheatre => 0.12,
+            BuildingType::House => 0.1,
+            BuildingType::Wall => 0.,
+            BuildingType::Prefecture => 0.02,
+            BuildingType::EngineersPost => 0.08,
+            BuildingType::Reservoir | BuildingType::Aqueduct | BuildingType::Fountain => 0.,
+            BuildingType::Farm => 0.08,
+            BuildingType::Granary => 0.1,
+            BuildingType::Orchard | BuildingType::PigFarm => 0.08,
+            BuildingType::Wharf => 0.06,
+            BuildingType::Market => 0.1,
+            BuildingType::TradePost | BuildingType::Dock => 0.08,
+            BuildingType::LumberCamp => 0.12,
+            BuildingType::Quarry => 0.02,
+        }
+    }
+
+    /// Damage gained per month, the building collapses when it reaches 1
+    pub fn damage_risk(&self) -> f32 {
+        match self {
+            BuildingType::Theatre | BuildingType::Amphitheatre => 0.1,
+            BuildingType::House => 0.08,
+            BuildingType::Wall => 0.04,
+            BuildingType::Prefecture => 0.08,
+            BuildingType::EngineersPost => 0.02,
+            BuildingType::Reservoir => 0.05,
+            BuildingType::Aqueduct => 0.03,
+            BuildingType::Fountain => 0.04,
+            BuildingType::Farm => 0.04,
+            BuildingType::Granary => 0.05,
+            BuildingType::Orchard | BuildingType::PigFarm => 0.04,
+            BuildingType::Wharf => 0.06,
+            BuildingType::Market => 0.05,
+            BuildingType::TradePost => 0.05,
+            BuildingType::Dock => 0.06,
+            BuildingType::LumberCamp => 0.04,
+            BuildingType::Quarry => 0.08,
+        }
+    }
+
+    /// The walker this building sends around once it's finished
+    pub fn walker(&self) -> Option<WalkerKind> {
+        match self {
+            BuildingType::Prefecture => Some(WalkerKind::Prefect),
+            BuildingType::EngineersPost => Some(WalkerKind::Engineer),
+            _ => None,
+        }
+    }
+
+    /// Condition lost per month
+    pub fn decay(&self) -> f32 {
+        match self {
+            BuildingType::Theatre | BuildingType::Amphitheatre => 0.04,
+            BuildingType::House => 0.03,
+            BuildingType::Wall => 0.01,
+            BuildingType::Prefecture | BuildingType::EngineersPost => 0.03,
+            BuildingType::Reservoir | BuildingType::Fountain => 0.02,
+            BuildingType::Aqueduct => 0.01,
+            BuildingType::Farm | BuildingType::Granary => 0.03,
+            BuildingType::Orchard | BuildingType::PigFarm => 0.03,
+            BuildingType::Wharf | BuildingType::Market => 0.03,
+            BuildingType::TradePost | BuildingType::Dock => 0.03,
+            BuildingType::LumberCamp | BuildingType::Quarry => 0.03,
+        }
+    }
+
+    /// Gold paid each month to keep the building running
+    pub fn upkeep(&self) -> u32 {
+        match self {
+            BuildingType::Theatre => 5,
+            BuildingType::Amphitheatre => 10,
+            BuildingType::House => 1,
+            BuildingType::Wall => 0,
+            BuildingType::Prefecture | BuildingType::EngineersPost => 3,
+            BuildingType::Reservoir => 4,
+            BuildingType::Aqueduct => 0,
+            BuildingType::Fountain => 1,
+            BuildingType::Farm | BuildingType::Granary => 2,
+            BuildingType::Orchard | BuildingType::PigFarm => 2,
+            BuildingType::Wharf | BuildingType::Market => 2,
+            BuildingType::TradePost | BuildingType::Dock => 3,
+            BuildingType::LumberCamp | BuildingType::Quarry => 2,
+        }
+    }
+
+    /// How much nicer the building makes living nearby, negative for nuisances,
+    /// and how many tiles that reaches, see `desirability::effect`. Only shown to
+    /// the player as an estimate, the simulation doesn't go by it
+    pub fn desirability(&self) -> (i32, u32) {
+        match self {
+            BuildingType::Theatre => (4, 3),
+            BuildingType::Amphitheatre => (6, 4),
+            BuildingType::House => (0, 0),
+            BuildingType::Wall => (1, 1),
+            BuildingType::Prefecture | BuildingType::EngineersPost => (1, 1),
+            BuildingType::Reservoir => (2, 2),
+            BuildingType::Aqueduct => (0, 0),
+            BuildingType::Fountain => (3, 2),
+            BuildingType::Farm | BuildingType::Orchard => (-1, 1),
+            BuildingType::PigFarm => (-4, 3),
+            BuildingType::Granary => (-2, 2),
+            BuildingType::Wharf => (-3, 2),
+            BuildingType::Market => (1, 2),
+            BuildingType::TradePost => (-2, 2),
+            BuildingType::Dock => (-3, 3),
+            BuildingType::LumberCamp => (-2, 2),
+            BuildingType::Quarry => (-4, 3),
+        }
+    }
+
+    /// Goods the building can keep, in total
+    pub fn storage_capacity(&self) -> u32 {
+        match self {
+            BuildingType::Granary => 200,
+            BuildingType::Market => 100,
+            _ => 0,
+        }
+    }
+
+    /// The food a farm grows on its field
+    pub fn crop(&self) -> Option<Good> {
+        match self {
+            BuildingType::Farm => Some(Good::Wheat),
+            BuildingType::Orchard => Some(Good::Fruit),
+            BuildingType::PigFarm => Some(Good::Meat),
+            _ => None,
+        }
+    }
+
+    /// The construction material a lumber camp or quarry cuts
+    pub fn material(&self) -> Option<Good> {
+        match self {
+            BuildingType::LumberCamp => Some(Good::Timber),
+            BuildingType::Quarry => Some(Good::Stone),
+            _ => None,
+        }
+    }
+
+    /// Whether water flows through the building from a reservoir
+    pub fn carries_water(&self) -> bool {
+        matches!(
+            self,
+            BuildingType::Reservoir | BuildingType::Aqueduct | BuildingType::Fountain
+        )
+    }
+
+    /// How many tiles around it the building supplies with water, once connected
+    pub fn water_range(&self) -> Option<u32> {
+        match self {
+            BuildingType::Fountain => Some(3),
+            _ => None,
+        }
+    }
+
+    /// What the building can be upgraded to in place
+    pub fn upgrade(&self) -> Option<BuildingType> {
+        match self {
+            BuildingType::Theatre => Some(BuildingType::Amphitheatre),
+            _ => None,
+        }
+    }
+
+    /// How dragging lays out several of these while placing them
+    pub fn drag_shape(&self) -> DragShape {
+        match self {
+            BuildingType::Theatre | BuildingType::Amphitheatre => DragShape::Single,
+            BuildingType::House => DragShape::Area,
+            BuildingType::Wall => DragShape::Line,
+            BuildingType::Prefecture | BuildingType::EngineersPost => DragShape::Single,
+            BuildingType::Reservoir | BuildingType::Fountain => DragShape::Single,
+            BuildingType::Aqueduct => DragShape::Line,
+            BuildingType::Farm | BuildingType::Granary => DragShape::Single,
+            BuildingType::Orchard | BuildingType::PigFarm => DragShape::Single,
+            BuildingType::Wharf | BuildingType::Market => DragShape::Single,
+            BuildingType::TradePost | BuildingType::Dock => DragShape::Single,
+            BuildingType::LumberCamp | BuildingType::Quarry => DragShape::Single,
+        }
+    }
+
     pub fn name(&self) -> String {
         match self {
             BuildingType::Theatre => "Theatre".to_string(),
-            //            BuildingType::Amphiteatre => "Amphiteatre".to_string(),
+            BuildingType::Amphitheatre => "Amphitheatre".to_string(),
+            BuildingType::House => "House".to_string(),
+            BuildingType::Wall => "Wall".to_string(),
+            BuildingType::Prefecture => "Prefecture".to_string(),
+            BuildingType::EngineersPost => "Engineer's Post".to_string(),
+            BuildingType::Reservoir => "Reservoir".to_string(),
+            BuildingType::Aqueduct => "Aqueduct".to_string(),
+            BuildingType::Fountain => "Fountain".to_string(),
+            BuildingType::Farm => "Farm".to_string(),
+            BuildingType::Granary => "Granary".to_string(),
+            BuildingType::Orchard => "Orchard".to_string(),
+            BuildingType::PigFarm => "Pig Farm".to_string(),
+            BuildingType::Wharf => "Fishing Wharf".to_string(),
+            BuildingType::Market => "Market".to_string(),
+            BuildingType::TradePost => "Trade Post".to_string(),
+            BuildingType::Dock => "Dock".to_string(),
+            BuildingType::LumberCamp => "Lumber Camp".to_string(),
+            BuildingType::Quarry => "Quarry".to_string(),
             //            BuildingType::Colosseum => "Colosseum".to_string(),
         }
     }
+
+    pub fn category(&self) -> BuildingCategory {
+        match self {
+            BuildingType::House => BuildingCategory::Housing,
+            BuildingType::Theatre | BuildingType::Amphitheatre => BuildingCategory::Entertainment,
+            BuildingType::Wall | BuildingType::Prefecture | BuildingType::EngineersPost => {
+                BuildingCategory::Services
+            }
+            BuildingType::Reservoir | BuildingType::Aqueduct | BuildingType::Fountain => {
+                BuildingCategory::Water
+            }
+            BuildingType::Farm
+            | BuildingType::Granary
+            | BuildingType::Orchard
+            | BuildingType::PigFarm
+            | BuildingType::Wharf
+            | BuildingType::Market => BuildingCategory::Food,
+            BuildingType::TradePost | BuildingType::Dock => BuildingCategory::Trade,
+            BuildingType::LumberCamp | BuildingType::Quarry => BuildingCategory::Industry,
+        }
+    }
+}
+
+/// Broad kind of building, as the minimap colours them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildingCategory {
+    Housing,
+    Entertainment,
+    Services,
+    Water,
+    Food,
+    Trade,
+    Industry,
+}
+
+/// Which way a building faces on the isometric grid
+#[derive(
+    Component,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum Orientation {
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 4] = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+    ];
+
+    /// The next orientation, a quarter turn clockwise
+    pub fn rotated(&self) -> Self {
+        match self {
+            Orientation::North => Orientation::East,
+            Orientation::East => Orientation::South,
+            Orientation::South => Orientation::West,
+            Orientation::West => Orientation::North,
+        }
+    }
 }
 
 #[derive(Component, Clone)]
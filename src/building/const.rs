@@ -1,6 +0,0 @@
-pub enum ZIndex {
-    Terrain = 0,
-    Building = 1,
-    Villagers = 2,
-    BuildingMarker = 3,
-}
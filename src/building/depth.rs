@@ -0,0 +1,31 @@
+use bevy::math::Vec2;
+
+/// Depth each layer spans, enough for the biggest maps turned any way
+const LAYER_DEPTH: f32 = 200.;
+/// Depth between two sprites standing a pixel apart up the screen
+const DEPTH_PER_PIXEL: f32 = 0.01;
+/// Extra depth of walkers and traders, so that one standing on a tile of a
+/// building is drawn in front of it
+pub const WALKER_LIFT: f32 = DEPTH_PER_PIXEL / 2.;
+
+/// Layers sprites are drawn in, from the back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZIndex {
+    Terrain = 0,
+    /// Crops and anything else lying flat on the ground
+    Ground = 1,
+    /// Buildings, rubble and walkers, sorted among themselves by where they stand
+    Building = 2,
+    BuildingMarker = 3,
+}
+
+impl ZIndex {
+    /**
+     * Depth of a sprite in this layer standing on `ground`, as drawn on the screen.
+     * Sprites standing lower on the screen are nearer, so they're drawn in front
+     */
+    pub fn depth(self, ground: Vec2) -> f32 {
+        let within = (LAYER_DEPTH / 2. - ground.y * DEPTH_PER_PIXEL).clamp(0., LAYER_DEPTH - 1.);
+        self as u8 as f32 * LAYER_DEPTH + within
+    }
+}
@@ -0,0 +1,34 @@
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use super::Placement;
+
+/**
+* What the building at `placement` adds to how desirable `pos` is to live on,
+* fading out the further `pos` lies from its footprint
+*/
+pub fn effect(placement: &Placement, pos: TilePos) -> f32 {
+    let (value, range) = placement.building_type.desirability();
+    let (w, h) = placement.building_type.oriented_size(placement.orientation);
+    let gap = |at: u32, from: u32, size: u32| {
+        if at < from {
+            from - at
+        } else {
+            at.saturating_sub(from + size - 1)
+        }
+    };
+    let distance = gap(pos.x, placement.anchor.x, w).max(gap(pos.y, placement.anchor.y, h));
+    if distance > range {
+        return 0.;
+    }
+    value as f32 * (range + 1 - distance) as f32 / (range + 1) as f32
+}
+
+/**
+* How desirable `pos` is to live on, adding up what every building in `placements` makes of it
+*/
+pub fn at(pos: TilePos, placements: &[Placement]) -> f32 {
+    placements
+        .iter()
+        .map(|placement| effect(placement, pos))
+        .sum()
+}
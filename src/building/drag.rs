@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// How dragging the mouse lays out several buildings of the same type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragShape {
+    /// Only one building where the mouse is released
+    Single,
+    /// A row along whichever axis the drag went furthest
+    Line,
+    /// Every spot in the dragged rectangle
+    Area,
+}
+
+/// Tile the current placement drag started from, if the mouse is held down
+#[derive(Resource, Default)]
+pub struct BuildingDrag(pub Option<TilePos>);
+
+/**
+* Offsets from `start` towards `end`, one building size apart
+*/
+fn steps(start: u32, end: u32, size: u32) -> Vec<u32> {
+    let count = start.abs_diff(end) / size;
+    (0..=count)
+        .map(|i| {
+            if end >= start {
+                start + i * size
+            } else {
+                start - i * size
+            }
+        })
+        .collect()
+}
+
+/**
+* Anchors of the buildings laid out by dragging from `start` to `end`,
+* for buildings with a footprint of `size`
+*/
+pub fn drag_anchors(
+    shape: DragShape,
+    start: TilePos,
+    end: TilePos,
+    size: (u32, u32),
+) -> Vec<TilePos> {
+    let (w, h) = size;
+    match shape {
+        DragShape::Single => vec![end],
+        DragShape::Line if start.x.abs_diff(end.x) >= start.y.abs_diff(end.y) => {
+            steps(start.x, end.x, w)
+                .into_iter()
+                .map(|x| TilePos { x, y: start.y })
+                .collect()
+        }
+        DragShape::Line => steps(start.y, end.y, h)
+            .into_iter()
+            .map(|y| TilePos { x: start.x, y })
+            .collect(),
+        DragShape::Area => steps(start.x, end.x, w)
+            .into_iter()
+            .flat_map(|x| {
+                steps(start.y, end.y, h)
+                    .into_iter()
+                    .map(move |y| TilePos { x, y })
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(positions: &[(u32, u32)]) -> Vec<TilePos> {
+        positions.iter().map(|&(x, y)| TilePos { x, y }).collect()
+    }
+
+    #[test]
+    fn steps_count_down_when_dragged_backwards() {
+        assert_eq!(steps(2, 7, 2), vec![2, 4, 6]);
+        assert_eq!(steps(7, 2, 2), vec![7, 5, 3]);
+    }
+
+    #[test]
+    fn line_dragged_backwards_starts_at_the_press() {
+        let anchors = drag_anchors(
+            DragShape::Line,
+            TilePos { x: 6, y: 3 },
+            TilePos { x: 2, y: 3 },
+            (1, 1),
+        );
+        assert_eq!(anchors, tiles(&[(6, 3), (5, 3), (4, 3), (3, 3), (2, 3)]));
+    }
+
+    #[test]
+    fn one_tile_drag_places_one_building() {
+        let pos = TilePos { x: 4, y: 4 };
+        for shape in [DragShape::Single, DragShape::Line, DragShape::Area] {
+            assert_eq!(
+                drag_anchors(shape, pos, pos, (2, 2)),
+                vec![pos],
+                "{shape:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn single_places_where_the_mouse_is_released() {
+        let anchors = drag_anchors(
+            DragShape::Single,
+            TilePos { x: 1, y: 1 },
+            TilePos { x: 5, y: 2 },
+            (1, 1),
+        );
+        assert_eq!(anchors, tiles(&[(5, 2)]));
+    }
+
+    #[test]
+    fn area_steps_by_a_two_by_two_footprint() {
+        let anchors = drag_anchors(
+            DragShape::Area,
+            TilePos { x: 0, y: 0 },
+            TilePos { x: 4, y: 3 },
+            (2, 2),
+        );
+        assert_eq!(
+            anchors,
+            tiles(&[(0, 0), (0, 2), (2, 0), (2, 2), (4, 0), (4, 2)])
+        );
+    }
+
+    #[test]
+    fn diagonal_line_snaps_to_the_longer_axis() {
+        let along_x = drag_anchors(
+            DragShape::Line,
+            TilePos { x: 1, y: 1 },
+            TilePos { x: 4, y: 3 },
+            (1, 1),
+        );
+        assert_eq!(along_x, tiles(&[(1, 1), (2, 1), (3, 1), (4, 1)]));
+
+        let along_y = drag_anchors(
+            DragShape::Line,
+            TilePos { x: 5, y: 6 },
+            TilePos { x: 4, y: 2 },
+            (1, 2),
+        );
+        assert_eq!(along_y, tiles(&[(5, 6), (5, 4), (5, 2)]));
+    }
+}
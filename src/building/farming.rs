@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use super::components::{Building, BuildingType};
+use super::site::{Finished, UnderConstruction};
+use super::storage::Storage;
+use super::upkeep::Condition;
+use super::Construction;
+use crate::resources::Good;
+use crate::time::{Calendar, GameTimer, NewMonth, Season};
+
+/// Months of growing seasons it takes crops to ripen
+const GROWING_MONTHS: f32 = 6.;
+/// Food from one ripe tile of the most fertile land
+const TILE_YIELD: f32 = 10.;
+/// Fish a wharf in good condition brings in each month, whatever the season
+const WHARF_CATCH: f32 = 12.;
+
+/// From bare soil to ripe crops
+const CROP_SPRITES: [&str; 4] = [
+    "buildings/crops_0.png",
+    "buildings/crops_1.png",
+    "buildings/crops_2.png",
+    "buildings/crops_3.png",
+];
+
+/// Crops growing on a working farm
+#[derive(Component, Debug, Clone, Default)]
+pub struct Field {
+    /// From 0 (just sown) to 1 (ripe)
+    pub growth: f32,
+}
+
+impl Field {
+    fn stage(&self) -> usize {
+        ((self.growth * (CROP_SPRITES.len() - 1) as f32) as usize).min(CROP_SPRITES.len() - 1)
+    }
+}
+
+/// The crops drawn on one tile of a farm
+#[derive(Component)]
+pub struct CropSprite {
+    /// Index in `CROP_SPRITES`
+    stage: usize,
+    pub tile: TilePos,
+}
+
+/**
+* Sows a field on every farm that just got finished, with crops drawn on its tiles
+*/
+#[allow(clippy::type_complexity)]
+pub(super) fn sow_fields(
+    mut construction: Construction,
+    farm_q: Query<
+        (Entity, &BuildingType),
+        (With<Building>, Without<UnderConstruction>, Without<Field>),
+    >,
+) {
+    for (farm, building_type) in &farm_q {
+        if building_type.crop().is_none() {
+            continue;
+        }
+        let Some(origin) = construction
+            .placement(farm)
+            .and_then(|placement| construction.translation(&placement))
+        else {
+            continue;
+        };
+        let crops: Vec<(TilePos, Vec3)> = construction
+            .area(farm)
+            .into_iter()
+            .filter_map(|pos| Some((pos, construction.tile_translation(pos)?)))
+            .map(|(pos, translation)| {
+                // Crops lie flat on the tile, under the farmhouse
+                (pos, (translation - origin).with_z(-0.5))
+            })
+            .collect();
+        construction
+            .commands
+            .entity(farm)
+            .insert(Field::default())
+            .with_children(|parent| {
+                for (tile, translation) in crops {
+                    parent.spawn((
+                        CropSprite { stage: 0, tile },
+                        SpriteBundle {
+                            texture: construction.asset_server.load(CROP_SPRITES[0]),
+                            transform: Transform::from_translation(translation),
+                            ..default()
+                        },
+                    ));
+                }
+            });
+    }
+}
+
+pub fn grow_crops(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    calendar: Res<Calendar>,
+    mut field_q: Query<&mut Field>,
+) {
+    if !calendar.season().is_growing() {
+        return;
+    }
+    let months = time.delta_seconds() / game_timer.0.duration().as_secs_f32();
+
+    for mut field in &mut field_q {
+        field.growth = (field.growth + months / GROWING_MONTHS).min(1.);
+    }
+}
+
+/**
+* Fills granaries with as much of `amount` as they have room for
+*/
+fn store_in_granaries(
+    granary_q: &mut Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+    good: Good,
+    mut amount: u32,
+) {
+    for (building_type, mut storage) in granary_q {
+        if amount == 0 {
+            break;
+        }
+        if *building_type == BuildingType::Granary {
+            amount -= storage.store(good, amount, building_type.storage_capacity());
+        }
+    }
+}
+
+/**
+* Brings in every field as the harvest season starts, filling granaries with what
+* the fertility of the land, the ripeness of the crops and the farm's condition allow
+*/
+pub(super) fn harvest(
+    mut new_month: EventReader<NewMonth>,
+    construction: Construction,
+    mut farm_q: Query<(Entity, &BuildingType, &Condition, &mut Field)>,
+    mut granary_q: Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+) {
+    for event in new_month.read() {
+        if Season::of(event.month) != Season::HARVEST || !Season::starts_at(event.month) {
+            continue;
+        }
+
+        for (farm, farm_type, condition, mut field) in &mut farm_q {
+            let Some(good) = farm_type.crop() else {
+                continue;
+            };
+            let fertility: f32 = construction
+                .area(farm)
+                .into_iter()
+                .filter_map(|pos| construction.terrain(pos))
+                .map(|terrain| terrain.fertility)
+                .sum();
+            let crop = (TILE_YIELD * fertility * field.growth * condition.efficiency()) as u32;
+            field.growth = 0.;
+
+            store_in_granaries(&mut granary_q, good, crop);
+        }
+    }
+}
+
+/**
+* Brings the month's catch of every working wharf to the granaries
+*/
+pub(super) fn catch_fish(
+    mut new_month: EventReader<NewMonth>,
+    wharf_q: Query<(&BuildingType, &Condition), Finished>,
+    mut granary_q: Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+) {
+    for _ in new_month.read() {
+        for (building_type, condition) in &wharf_q {
+            if *building_type == BuildingType::Wharf {
+                let catch = (WHARF_CATCH * condition.efficiency()) as u32;
+                store_in_granaries(&mut granary_q, Good::Fish, catch);
+            }
+        }
+    }
+}
+
+pub fn update_crop_sprites(
+    asset_server: Res<AssetServer>,
+    field_q: Query<&Field>,
+    mut crop_q: Query<(&Parent, &mut CropSprite, &mut Handle<Image>)>,
+) {
+    for (parent, mut crop, mut texture) in &mut crop_q {
+        let Ok(field) = field_q.get(parent.get()) else {
+            continue;
+        };
+        if crop.stage != field.stage() {
+            crop.stage = field.stage();
+            *texture = asset_server.load(CROP_SPRITES[crop.stage]);
+        }
+    }
+}
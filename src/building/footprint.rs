@@ -0,0 +1,176 @@
+use crate::grid::{Terrain, TerrainType};
+
+use super::components::Orientation;
+
+/// What the terrain under one cell of a footprint has to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellRule {
+    Land,
+    Water,
+}
+
+impl CellRule {
+    pub fn allows(&self, terrain: &Terrain) -> bool {
+        match self {
+            CellRule::Land => terrain.is_buildable,
+            CellRule::Water => terrain.terrain_type == TerrainType::Water,
+        }
+    }
+}
+
+/// The cells a building covers within its `width` × `height` bounding box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Footprint {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<(u32, u32, CellRule)>,
+}
+
+impl Footprint {
+    /**
+     * Reads a mask with one line per `y` and one char per `x`:
+     * `L` needs land, `W` needs water and `.` is left free
+     */
+    pub fn parse(mask: &str) -> Self {
+        let mut cells = vec![];
+        let mut width = 0;
+        let mut height = 0;
+
+        for (y, line) in mask.lines().enumerate() {
+            height = y as u32 + 1;
+            for (x, c) in line.chars().enumerate() {
+                width = width.max(x as u32 + 1);
+                let rule = match c {
+                    'L' => CellRule::Land,
+                    'W' => CellRule::Water,
+                    _ => continue,
+                };
+                cells.push((x as u32, y as u32, rule));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /**
+     * The same footprint after a quarter turn clockwise
+     */
+    fn rotated_once(&self) -> Self {
+        Self {
+            width: self.height,
+            height: self.width,
+            cells: self
+                .cells
+                .iter()
+                .map(|(x, y, rule)| (self.height - 1 - y, *x, *rule))
+                .collect(),
+        }
+    }
+
+    pub fn oriented(&self, orientation: Orientation) -> Self {
+        let turns = match orientation {
+            Orientation::North => 0,
+            Orientation::East => 1,
+            Orientation::South => 2,
+            Orientation::West => 3,
+        };
+
+        (0..turns).fold(self.clone(), |footprint, _| footprint.rotated_once())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An L of land with its inner corner on water
+    const L_SHAPE: &str = "L.\nLW\nLL";
+
+    fn sorted(mut cells: Vec<(u32, u32, CellRule)>) -> Vec<(u32, u32, CellRule)> {
+        cells.sort_by_key(|(x, y, _)| (*y, *x));
+        cells
+    }
+
+    #[test]
+    fn parses_cells_with_their_rules() {
+        let footprint = Footprint::parse(L_SHAPE);
+        assert_eq!((footprint.width, footprint.height), (2, 3));
+        assert_eq!(
+            sorted(footprint.cells),
+            vec![
+                (0, 0, CellRule::Land),
+                (0, 1, CellRule::Land),
+                (1, 1, CellRule::Water),
+                (0, 2, CellRule::Land),
+                (1, 2, CellRule::Land),
+            ]
+        );
+    }
+
+    #[test]
+    fn rotates_an_l_shape_clockwise() {
+        // L.      LLL
+        // LW  ->  LW.
+        // LL
+        let footprint = Footprint::parse(L_SHAPE).oriented(Orientation::East);
+        assert_eq!((footprint.width, footprint.height), (3, 2));
+        assert_eq!(
+            sorted(footprint.cells),
+            vec![
+                (0, 0, CellRule::Land),
+                (1, 0, CellRule::Land),
+                (2, 0, CellRule::Land),
+                (0, 1, CellRule::Land),
+                (1, 1, CellRule::Water),
+            ]
+        );
+    }
+
+    #[test]
+    fn half_turn_mirrors_both_axes() {
+        let footprint = Footprint::parse(L_SHAPE);
+        let turned = footprint.oriented(Orientation::South);
+        assert_eq!((turned.width, turned.height), (2, 3));
+        let expected: Vec<_> = footprint
+            .cells
+            .iter()
+            .map(|(x, y, rule)| (1 - x, 2 - y, *rule))
+            .collect();
+        assert_eq!(sorted(turned.cells), sorted(expected));
+    }
+
+    #[test]
+    fn four_turns_come_back_around() {
+        for mask in [L_SHAPE, "LLL\nL.L\nLLW", "LW"] {
+            let footprint = Footprint::parse(mask);
+            let turned = (0..4).fold(footprint.clone(), |footprint, _| footprint.rotated_once());
+            assert_eq!(sorted(turned.cells), sorted(footprint.cells));
+            assert_eq!(
+                (turned.width, turned.height),
+                (footprint.width, footprint.height)
+            );
+        }
+    }
+
+    #[test]
+    fn hollow_mask_keeps_its_hole() {
+        let footprint = Footprint::parse("LLL\nL.L\nLLW");
+        for orientation in Orientation::ALL {
+            let turned = footprint.oriented(orientation);
+            assert_eq!(turned.cells.len(), 8);
+            assert!(!turned.cells.iter().any(|(x, y, _)| (*x, *y) == (1, 1)));
+            assert_eq!(
+                turned
+                    .cells
+                    .iter()
+                    .filter(|(_, _, rule)| *rule == CellRule::Water)
+                    .count(),
+                1
+            );
+        }
+    }
+}
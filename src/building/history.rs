@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+
+use super::housing::Household;
+use super::risk::{OnFire, Risk};
+use super::site::UnderConstruction;
+use super::storage::Storage;
+use super::upkeep::Condition;
+use super::Placement;
+use crate::time::NewMonth;
+
+/// What a building held when it was demolished, given back if that's undone
+#[derive(Debug, Clone, Default)]
+pub struct BuildingState {
+    /// The construction site it was, if it wasn't finished
+    pub site: Option<UnderConstruction>,
+    pub storage: Storage,
+    pub condition: Condition,
+    pub risk: Risk,
+    pub household: Option<Household>,
+    pub on_fire: Option<OnFire>,
+}
+
+/// A construction or demolition that can still be taken back.
+#[derive(Debug, Clone)]
+pub enum BuildingAction {
+    Placed(Placement),
+    /// Along with everything the building held
+    Demolished(Placement, BuildingState),
+    /// Holding the building as it was before the upgrade
+    Upgraded(Placement),
+}
+
+/// Building actions of the current in-game month, most recent last.
+#[derive(Resource, Default)]
+pub struct UndoHistory {
+    pub undo: Vec<BuildingAction>,
+    pub redo: Vec<BuildingAction>,
+}
+
+impl UndoHistory {
+    /**
+     * Stores a new action, which makes whatever was undone before it unreachable
+     */
+    pub fn record(&mut self, action: BuildingAction) {
+        self.undo.push(action);
+        self.redo.clear();
+    }
+}
+
+/**
+* Only actions of the current month can be undone, so that refunds can't be abused late
+*/
+pub fn clear_history(mut new_month: EventReader<NewMonth>, mut history: ResMut<UndoHistory>) {
+    for _ in new_month.read() {
+        history.undo.clear();
+        history.redo.clear();
+    }
+}
@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+
+use super::components::{Building, BuildingType};
+use super::market::MARKET_RANGE;
+use super::risk::OnFire;
+use super::site::UnderConstruction;
+use super::storage::Storage;
+use super::Construction;
+use crate::resources::Good;
+use crate::time::NewMonth;
+
+/// People moving into a well fed house each month, while there's room
+const IMMIGRANTS: u32 = 2;
+/// Share of its residents a hungry house loses each month
+const EMIGRATION: f32 = 0.25;
+/// Months without enough food before the residents riot
+const RIOT_MONTHS: u32 = 3;
+
+/// How far a house has evolved, deciding how many people live there
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HouseLevel {
+    #[default]
+    Hut,
+    Cottage,
+    Villa,
+}
+
+impl HouseLevel {
+    pub fn capacity(&self) -> u32 {
+        match self {
+            HouseLevel::Hut => 4,
+            HouseLevel::Cottage => 8,
+            HouseLevel::Villa => 14,
+        }
+    }
+
+    /// Kinds of food the residents need to eat each month to live at this level
+    pub fn variety(&self) -> u32 {
+        match self {
+            HouseLevel::Hut => 0,
+            HouseLevel::Cottage => 2,
+            HouseLevel::Villa => 3,
+        }
+    }
+
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            HouseLevel::Hut => "buildings/house.png",
+            HouseLevel::Cottage => "buildings/cottage.png",
+            HouseLevel::Villa => "buildings/villa.png",
+        }
+    }
+
+    /// The highest level a diet of `variety` kinds of food allows
+    fn allowed_by(variety: u32) -> Self {
+        [HouseLevel::Villa, HouseLevel::Cottage]
+            .into_iter()
+            .find(|level| variety >= level.variety())
+            .unwrap_or(HouseLevel::Hut)
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            HouseLevel::Hut => "Hut".to_string(),
+            HouseLevel::Cottage => "Cottage".to_string(),
+            HouseLevel::Villa => "Villa".to_string(),
+        }
+    }
+}
+
+/// The people living in a finished house and how well they eat
+#[derive(Component, Debug, Clone, Default)]
+pub struct Household {
+    pub residents: u32,
+    pub level: HouseLevel,
+    /// Kinds of food eaten last month
+    pub variety: u32,
+    /// Months in a row the residents didn't get enough to eat
+    pub hungry_months: u32,
+}
+
+/**
+* Opens every house that just got finished to newcomers
+*/
+#[allow(clippy::type_complexity)]
+pub(super) fn move_in(
+    mut commands: Commands,
+    house_q: Query<
+        (Entity, &BuildingType),
+        (
+            With<Building>,
+            Without<UnderConstruction>,
+            Without<Household>,
+        ),
+    >,
+) {
+    for (house, building_type) in &house_q {
+        if *building_type == BuildingType::House {
+            commands.entity(house).insert(Household::default());
+        }
+    }
+}
+
+/**
+* Feeds every household from the nearest market each month, one food per
+* resident shared out evenly over the kinds of food on sale
+*/
+pub(super) fn feed_households(
+    mut new_month: EventReader<NewMonth>,
+    construction: Construction,
+    mut house_q: Query<(Entity, &mut Household)>,
+    mut market_q: Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+) {
+    for _ in new_month.read() {
+        for (house, mut household) in &mut house_q {
+            let area = construction.area(house);
+            let market = construction
+                .buildings_around(&area, MARKET_RANGE)
+                .into_iter()
+                .find(|building| {
+                    market_q
+                        .get(*building)
+                        .is_ok_and(|(building_type, _)| *building_type == BuildingType::Market)
+                });
+
+            let mut hunger = household.residents;
+            let mut variety = 0;
+            if let Some((_, mut stock)) = market.and_then(|market| market_q.get_mut(market).ok()) {
+                let on_sale: Vec<Good> = Good::FOOD
+                    .into_iter()
+                    .filter(|good| stock.amount(*good) > 0)
+                    .collect();
+                for (i, good) in on_sale.iter().enumerate() {
+                    let share = hunger.div_ceil((on_sale.len() - i) as u32);
+                    let eaten = stock.take(*good, share);
+                    if eaten > 0 {
+                        variety += 1;
+                    }
+                    hunger -= eaten;
+                }
+                // Make up for whatever ran short with any food left
+                for good in on_sale {
+                    hunger -= stock.take(good, hunger);
+                }
+            }
+
+            household.variety = variety;
+            if hunger > 0 {
+                household.hungry_months += 1;
+            } else {
+                household.hungry_months = 0;
+            }
+        }
+    }
+}
+
+/**
+* Moves people in and out of houses depending on how well they ate,
+* evolving houses whose residents enjoy enough variety and rioting
+* in those that went hungry for too long
+*/
+pub(super) fn settle_households(
+    mut new_month: EventReader<NewMonth>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut house_q: Query<(Entity, &mut Household, &mut Handle<Image>, Has<OnFire>)>,
+) {
+    for _ in new_month.read() {
+        for (house, mut household, mut texture, burning) in &mut house_q {
+            let level = if household.hungry_months == 0 {
+                let arrivals = household
+                    .level
+                    .capacity()
+                    .saturating_sub(household.residents)
+                    .min(IMMIGRANTS);
+                household.residents += arrivals;
+                HouseLevel::allowed_by(household.variety)
+            } else {
+                let leaving = (household.residents as f32 * EMIGRATION).ceil() as u32;
+                household.residents -= leaving;
+                if household.hungry_months >= RIOT_MONTHS && household.residents > 0 && !burning {
+                    warn!("Hungry residents riot and set their house on fire");
+                    commands.entity(house).insert(OnFire::default());
+                }
+                HouseLevel::Hut
+            };
+
+            if level != household.level {
+                household.level = level;
+                household.residents = household.residents.min(level.capacity());
+                *texture = asset_server.load(level.sprite());
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+
+use super::components::BuildingType;
+use super::site::Finished;
+use super::upkeep::Condition;
+use crate::resources::GlobalResources;
+use crate::time::NewMonth;
+
+/// Units of its material a lumber camp or quarry in perfect condition cuts each month
+const MONTHLY_OUTPUT: f32 = 5.;
+
+/**
+* Adds what every working lumber camp and quarry cut this month to the city stockpile
+*/
+pub(super) fn produce_materials(
+    mut new_month: EventReader<NewMonth>,
+    mut resources: ResMut<GlobalResources>,
+    producer_q: Query<(&BuildingType, &Condition), Finished>,
+) {
+    for _ in new_month.read() {
+        for (building_type, condition) in &producer_q {
+            let Some(good) = building_type.material() else {
+                continue;
+            };
+            let output = (MONTHLY_OUTPUT * condition.efficiency()) as u32;
+            *resources.goods.entry(good).or_insert(0) += output;
+        }
+    }
+}
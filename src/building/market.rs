@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use super::components::BuildingType;
+use super::site::UnderConstruction;
+use super::storage::Storage;
+use crate::resources::Good;
+use crate::time::NewMonth;
+
+/// How many tiles around it a market sells food to houses
+pub const MARKET_RANGE: u32 = 6;
+
+/**
+* Sends the buyers of every working market to the granaries each month,
+* stocking up on an even share of every kind of food
+*/
+pub(super) fn supply_markets(
+    mut new_month: EventReader<NewMonth>,
+    mut store_q: Query<(Entity, &BuildingType, &mut Storage), Without<UnderConstruction>>,
+) {
+    for _ in new_month.read() {
+        let markets: Vec<Entity> = store_q
+            .iter()
+            .filter(|(_, building_type, _)| **building_type == BuildingType::Market)
+            .map(|(entity, _, _)| entity)
+            .collect();
+        let share = BuildingType::Market.storage_capacity() / Good::FOOD.len() as u32;
+
+        for market in markets {
+            for good in Good::FOOD {
+                let Ok((_, _, storage)) = store_q.get(market) else {
+                    continue;
+                };
+                let mut wanted = share.saturating_sub(storage.amount(good));
+
+                let mut bought = 0;
+                for (_, building_type, mut granary) in &mut store_q {
+                    if wanted == 0 {
+                        break;
+                    }
+                    if *building_type == BuildingType::Granary {
+                        let taken = granary.take(good, wanted);
+                        wanted -= taken;
+                        bought += taken;
+                    }
+                }
+
+                if let Ok((_, building_type, mut storage)) = store_q.get_mut(market) {
+                    storage.store(good, bought, building_type.storage_capacity());
+                }
+            }
+        }
+    }
+}
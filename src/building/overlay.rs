@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+use super::components::{Building, BuildingType};
+use super::risk::{OnFire, Risk};
+use super::water::{WaterConnected, WaterCoverage};
+
+/// What buildings are tinted by, to see how the city is doing at a glance
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overlay {
+    #[default]
+    None,
+    /// How close buildings are to catching fire or collapsing
+    Risk,
+    /// Which water buildings are connected and which houses have water
+    Water,
+}
+
+impl Overlay {
+    pub const ALL: [Overlay; 3] = [Overlay::None, Overlay::Risk, Overlay::Water];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overlay::None => "None",
+            Overlay::Risk => "Risk",
+            Overlay::Water => "Water",
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            Overlay::None => Overlay::Risk,
+            Overlay::Risk => Overlay::Water,
+            Overlay::Water => Overlay::None,
+        }
+    }
+}
+
+const WATERED: Color = Color::srgb(0.5, 0.7, 1.);
+const DRY: Color = Color::srgb(1., 0.55, 0.55);
+
+/**
+* Tints buildings on fire, and every other building by the current overlay
+*/
+#[allow(clippy::type_complexity)]
+pub fn tint_buildings(
+    overlay: Res<Overlay>,
+    mut building_q: Query<
+        (
+            &BuildingType,
+            &Risk,
+            Option<&OnFire>,
+            Has<WaterConnected>,
+            Has<WaterCoverage>,
+            &mut Sprite,
+        ),
+        With<Building>,
+    >,
+) {
+    for (building_type, risk, fire, connected, covered, mut sprite) in &mut building_q {
+        sprite.color = match *overlay {
+            _ if fire.is_some() => Color::srgb(1., 0.45, 0.2),
+            Overlay::None => Color::WHITE,
+            Overlay::Risk => {
+                let level = risk.fire.max(risk.damage).clamp(0., 1.);
+                Color::srgb(0.3 + 0.7 * level, 1. - 0.7 * level, 0.3)
+            }
+            Overlay::Water if building_type.carries_water() => {
+                if connected {
+                    WATERED
+                } else {
+                    DRY
+                }
+            }
+            Overlay::Water if *building_type == BuildingType::House => {
+                if covered {
+                    WATERED
+                } else {
+                    DRY
+                }
+            }
+            Overlay::Water => Color::srgb(0.6, 0.6, 0.6),
+        };
+    }
+}
+
+pub fn cycle_overlay(keys: Res<ButtonInput<KeyCode>>, mut overlay: ResMut<Overlay>) {
+    if keys.just_pressed(KeyCode::KeyO) {
+        *overlay = overlay.next();
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::resources::Good;
+use crate::time::NewMonth;
+
+/// Change in a good's price for every unit the city sells or buys
+const TRADE_EFFECT: f32 = 0.005;
+/// Share of the way back to usual prices covered each month
+const RECOVERY: f32 = 0.1;
+/// Chance each month of a shortage or glut of some good
+const EVENT_CHANCE: f32 = 0.1;
+const MIN_FACTOR: f32 = 0.3;
+const MAX_FACTOR: f32 = 3.;
+/// Months of prices kept for the chart
+pub const HISTORY_MONTHS: usize = 24;
+
+/// How the prices traders ask and pay compare to the usual ones in the level file
+#[derive(Resource, Debug)]
+pub struct MarketPrices {
+    /// Multiplies the usual price of each good, 1 when missing
+    pub factors: BTreeMap<Good, f32>,
+    /// Factors at the start of each of the last months, oldest first
+    pub history: VecDeque<BTreeMap<Good, f32>>,
+    /// Where shortages and gluts come from, kept here so replays see the same ones
+    rng: u64,
+}
+
+impl Default for MarketPrices {
+    fn default() -> Self {
+        Self {
+            factors: BTreeMap::new(),
+            history: VecDeque::new(),
+            rng: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+}
+
+impl MarketPrices {
+    pub fn factor(&self, good: Good) -> f32 {
+        self.factors.get(&good).copied().unwrap_or(1.)
+    }
+
+    /**
+     * What a trader pays or asks for `good` right now, given its `usual` price
+     */
+    pub fn price(&self, good: Good, usual: u32) -> u32 {
+        ((usual as f32 * self.factor(good)).round() as u32).max(1)
+    }
+
+    /**
+     * Lowers the price of goods the city sells and raises that of goods it buys,
+     * `amount` being negative for sales
+     */
+    pub fn trade(&mut self, good: Good, amount: i32) {
+        let factor = self.factor(good) + amount as f32 * TRADE_EFFECT;
+        self.factors
+            .insert(good, factor.clamp(MIN_FACTOR, MAX_FACTOR));
+    }
+
+    /// The hashable part of the prices, for `state_hash`
+    pub fn state(&self) -> (Vec<(Good, u32)>, u64) {
+        let factors = self
+            .factors
+            .iter()
+            .map(|(good, factor)| (*good, factor.to_bits()))
+            .collect();
+        (factors, self.rng)
+    }
+
+    /**
+     * Next number from 0 to 1, from a xorshift sequence
+     */
+    fn roll(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % 10_000) as f32 / 10_000.
+    }
+}
+
+/**
+* Eases prices back towards the usual ones each month, now and then
+* upset by a shortage or glut, and keeps track of them for the chart
+*/
+pub fn update_prices(mut new_month: EventReader<NewMonth>, mut prices: ResMut<MarketPrices>) {
+    for _ in new_month.read() {
+        let goods: Vec<Good> = prices.factors.keys().copied().collect();
+        for good in goods {
+            let factor = prices.factor(good);
+            prices
+                .factors
+                .insert(good, factor + (1. - factor) * RECOVERY);
+        }
+
+        if prices.roll() < EVENT_CHANCE {
+            let good = Good::ALL[(prices.roll() * Good::ALL.len() as f32) as usize];
+            let (shock, event) = if prices.roll() < 0.5 {
+                (1.5, "shortage")
+            } else {
+                (0.6, "glut")
+            };
+            let factor = (prices.factor(good) * shock).clamp(MIN_FACTOR, MAX_FACTOR);
+            prices.factors.insert(good, factor);
+            info!("A {} of {} moves its price", event, good.name());
+        }
+
+        let factors = Good::ALL
+            .into_iter()
+            .map(|good| (good, prices.factor(good)))
+            .collect();
+        prices.history.push_back(factors);
+        if prices.history.len() > HISTORY_MONTHS {
+            prices.history.pop_front();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use super::components::{Building, BuildingType};
+use super::site::UnderConstruction;
+use super::water::WaterCoverage;
+use super::Construction;
+use crate::time::GameTimer;
+
+pub const RUBBLE_SPRITE: &str = "buildings/rubble.png";
+
+/// Gold it takes to clear one tile of rubble
+pub const RUBBLE_CLEARING_COST: u32 = 2;
+
+/// Months a fire takes to burn a building down
+const BURN_MONTHS: f32 = 0.5;
+/// Share of its usual fire risk a building with water coverage gains
+const WATERED_FIRE_RISK: f32 = 0.5;
+/// Fire risk per month added to the buildings next to a fire
+const FIRE_SPREAD: f32 = 3.;
+
+/// How close a building is to catching fire or collapsing, each from 0 to 1
+#[derive(Component, Debug, Clone, Default)]
+pub struct Risk {
+    pub fire: f32,
+    pub damage: f32,
+}
+
+/// A building on fire, it burns down once `burnt` reaches 1
+#[derive(Component, Debug, Clone, Default)]
+pub struct OnFire {
+    pub burnt: f32,
+}
+
+/// What's left of a tile after a building collapsed or burnt down.
+/// It has to be cleared before building there again.
+#[derive(Component)]
+pub struct Rubble;
+
+/**
+* Raises the risk of every working building, setting it on fire or
+* collapsing it when it gets too high
+*/
+#[allow(clippy::type_complexity)]
+pub(super) fn accumulate_risk(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    mut construction: Construction,
+    mut risk_q: Query<
+        (Entity, &BuildingType, &mut Risk, Has<WaterCoverage>),
+        (With<Building>, Without<UnderConstruction>, Without<OnFire>),
+    >,
+) {
+    let months = time.delta_seconds() / game_timer.0.duration().as_secs_f32();
+
+    for (entity, building_type, mut risk, watered) in &mut risk_q {
+        // Water close at hand keeps small fires from spreading through the building
+        let fire_risk = if watered {
+            building_type.fire_risk() * WATERED_FIRE_RISK
+        } else {
+            building_type.fire_risk()
+        };
+        risk.fire += fire_risk * months;
+        risk.damage += building_type.damage_risk() * months;
+
+        if risk.fire >= 1. {
+            construction
+                .commands
+                .entity(entity)
+                .insert(OnFire::default());
+        } else if risk.damage >= 1. {
+            construction.collapse(entity);
+        }
+    }
+}
+
+/**
+* Burns buildings on fire down to rubble, heating up their neighbours
+* until they catch fire too
+*/
+pub(super) fn burn_buildings(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    mut construction: Construction,
+    mut fire_q: Query<(Entity, &mut OnFire)>,
+    mut risk_q: Query<&mut Risk, Without<UnderConstruction>>,
+) {
+    let months = time.delta_seconds() / game_timer.0.duration().as_secs_f32();
+
+    for (entity, mut fire) in &mut fire_q {
+        fire.burnt += months / BURN_MONTHS;
+
+        let area = construction.area(entity);
+        for neighbour in construction.buildings_around(&area, 1) {
+            if neighbour == entity {
+                continue;
+            }
+            if let Ok(mut risk) = risk_q.get_mut(neighbour) {
+                risk.fire += FIRE_SPREAD * months;
+            }
+        }
+
+        if fire.burnt >= 1. {
+            construction.collapse(entity);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use super::components::{Building, BuildingType, Orientation};
+use super::water::WaterNetworkDirty;
+use crate::resources::{GlobalResources, Good};
+use crate::time::GameTimer;
+
+pub const SCAFFOLDING_SPRITE: &str = "buildings/scaffolding.png";
+
+const PROGRESS_BAR_SIZE: Vec2 = Vec2::new(60., 6.);
+const PROGRESS_BAR_HEIGHT: f32 = 110.;
+
+/// A building that has been placed but doesn't work yet.
+#[derive(Component, Debug, Clone, Default)]
+pub struct UnderConstruction {
+    /// From 0 to 1
+    pub progress: f32,
+    pub delivered: BTreeMap<Good, u32>,
+}
+
+/// Query filter for buildings that are finished and working
+pub type Finished = (With<Building>, Without<UnderConstruction>);
+
+impl UnderConstruction {
+    /**
+     * Share of the materials needed by `building_type` that has arrived,
+     * which is as far as construction can get for now
+     */
+    pub fn delivered_share(&self, building_type: BuildingType) -> f32 {
+        building_type
+            .materials()
+            .iter()
+            .map(|(good, needed)| {
+                let delivered = self.delivered.get(good).copied().unwrap_or(0);
+                delivered as f32 / *needed as f32
+            })
+            .fold(1., f32::min)
+    }
+}
+
+#[derive(Component)]
+pub struct ProgressBarFill;
+
+pub fn progress_bar(parent: &mut ChildBuilder) {
+    parent.spawn(SpriteBundle {
+        sprite: Sprite {
+            color: Color::srgba(0., 0., 0., 0.7),
+            custom_size: Some(PROGRESS_BAR_SIZE),
+            ..default()
+        },
+        transform: Transform::from_xyz(0., PROGRESS_BAR_HEIGHT, 0.1),
+        ..default()
+    });
+    parent.spawn((
+        ProgressBarFill,
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::srgb(0.9, 0.7, 0.1),
+                custom_size: Some(Vec2::new(0., PROGRESS_BAR_SIZE.y)),
+                anchor: Anchor::CenterLeft,
+                ..default()
+            },
+            transform: Transform::from_xyz(-PROGRESS_BAR_SIZE.x / 2., PROGRESS_BAR_HEIGHT, 0.2),
+            ..default()
+        },
+    ));
+}
+
+/**
+* Delivers materials from the stockpile and advances construction,
+* turning finished sites into working buildings
+*/
+pub fn progress_construction(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut resources: ResMut<GlobalResources>,
+    mut water_dirty: ResMut<WaterNetworkDirty>,
+    mut site_q: Query<(
+        Entity,
+        &BuildingType,
+        &Orientation,
+        &mut UnderConstruction,
+        &mut Handle<Image>,
+    )>,
+) {
+    let month = game_timer.0.duration().as_secs_f32();
+
+    for (entity, building_type, orientation, mut site, mut texture) in &mut site_q {
+        for (good, needed) in building_type.materials() {
+            let delivered = site.delivered.entry(*good).or_insert(0);
+            let stock = resources.goods.entry(*good).or_insert(0);
+            let amount = (*needed - *delivered).min(*stock);
+            *delivered += amount;
+            *stock -= amount;
+        }
+
+        let step = time.delta_seconds() / (month * building_type.build_months());
+        site.progress = (site.progress + step).min(site.delivered_share(*building_type));
+
+        if site.progress >= 1. {
+            let (sprite, _) = building_type.oriented_sprite(*orientation);
+            *texture = asset_server.load(sprite);
+            commands
+                .entity(entity)
+                .remove::<UnderConstruction>()
+                .despawn_descendants();
+            water_dirty.0 = true;
+        }
+    }
+}
+
+pub fn update_progress_bars(
+    site_q: Query<&UnderConstruction>,
+    mut bar_q: Query<(&Parent, &mut Sprite), With<ProgressBarFill>>,
+) {
+    for (parent, mut sprite) in &mut bar_q {
+        if let Ok(site) = site_q.get(parent.get()) {
+            sprite.custom_size = Some(Vec2::new(
+                PROGRESS_BAR_SIZE.x * site.progress,
+                PROGRESS_BAR_SIZE.y,
+            ));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::resources::Good;
+
+/// Goods kept in a building, up to its `BuildingType::storage_capacity`
+#[derive(Component, Debug, Clone, Default)]
+pub struct Storage {
+    pub goods: BTreeMap<Good, u32>,
+}
+
+impl Storage {
+    pub fn total(&self) -> u32 {
+        self.goods.values().sum()
+    }
+
+    /**
+     * Stores as much of `amount` as fits in `capacity`, returning how much did
+     */
+    pub fn store(&mut self, good: Good, amount: u32, capacity: u32) -> u32 {
+        let stored = amount.min(capacity.saturating_sub(self.total()));
+        if stored > 0 {
+            *self.goods.entry(good).or_insert(0) += stored;
+        }
+        stored
+    }
+
+    /**
+     * Takes up to `amount` of `good` out, returning how much there was
+     */
+    pub fn take(&mut self, good: Good, amount: u32) -> u32 {
+        let Some(kept) = self.goods.get_mut(&good) else {
+            return 0;
+        };
+        let taken = amount.min(*kept);
+        *kept -= taken;
+        if *kept == 0 {
+            self.goods.remove(&good);
+        }
+        taken
+    }
+
+    pub fn amount(&self, good: Good) -> u32 {
+        self.goods.get(&good).copied().unwrap_or(0)
+    }
+}
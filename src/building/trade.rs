@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+
+use super::components::{AnchorTile, BuildingType};
+use super::prices::MarketPrices;
+use super::site::{Finished, UnderConstruction};
+use super::storage::Storage;
+use super::Construction;
+use crate::command::PlayerCommand;
+use crate::grid::Level;
+use crate::resources::{Good, Ledger, LedgerEntry};
+use crate::time::{GameTimer, NewMonth};
+
+/// Months a caravan or ship stays at the trade post or dock
+const TRADER_STAY_MONTHS: f32 = 0.5;
+
+/// How goods reach a trade partner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TradeRoute {
+    /// Caravans, arriving at a trade post
+    Land,
+    /// Ships, arriving at a dock
+    Sea,
+}
+
+impl TradeRoute {
+    /// What the city needs for traders on this route to come
+    pub fn building(&self) -> BuildingType {
+        match self {
+            TradeRoute::Land => BuildingType::TradePost,
+            TradeRoute::Sea => BuildingType::Dock,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            TradeRoute::Land => Color::srgb(0.6, 0.4, 0.2),
+            TradeRoute::Sea => Color::srgb(0.9, 0.9, 0.8),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TradeRoute::Land => "Land",
+            TradeRoute::Sea => "Sea",
+        }
+    }
+}
+
+/// A neighbouring city the level lets the player trade with
+#[derive(Debug, Clone, Deserialize)]
+pub struct TradePartner {
+    pub name: String,
+    pub route: TradeRoute,
+    /// Months between two visits of its traders
+    pub every: u32,
+    /// Goods its traders carry at most, both ways together
+    pub capacity: u32,
+    /// Where it lies on the world map, east and north of the city
+    #[serde(default)]
+    pub position: [f32; 2],
+    /// Usual gold paid for each good it buys from the city, see `MarketPrices`
+    #[serde(default)]
+    pub buys: BTreeMap<Good, u32>,
+    /// Usual gold asked for each good it sells to the city
+    #[serde(default)]
+    pub sells: BTreeMap<Good, u32>,
+}
+
+impl TradePartner {
+    /**
+     * Months left until its traders come, counting from `month`
+     */
+    pub fn next_visit(&self, month: u32) -> u32 {
+        let every = self.every.max(1);
+        every - month % every
+    }
+}
+
+/// What the city does with a good when traders come
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TradeSetting {
+    #[default]
+    None,
+    /// Buy until the city has this much
+    Import(u32),
+    /// Sell whatever the city has above this much
+    Export(u32),
+}
+
+/// Import and export settings, per good
+#[derive(Resource, Default, Debug)]
+pub struct TradeSettings(pub BTreeMap<Good, TradeSetting>);
+
+/// A caravan or ship waiting at a trade post or dock
+#[derive(Component)]
+pub struct Trader {
+    /// Where it waits
+    pub tile: TilePos,
+    months_left: f32,
+}
+
+pub(super) fn apply_trade_settings(
+    mut player_commands: EventReader<PlayerCommand>,
+    mut settings: ResMut<TradeSettings>,
+) {
+    for player_command in player_commands.read() {
+        if let PlayerCommand::SetTrade { good, setting } = *player_command {
+            settings.0.insert(good, setting);
+        }
+    }
+}
+
+/**
+* How much of `good` the city has, in the stockpile or its granaries
+*/
+fn in_stock(
+    construction: &Construction,
+    granary_q: &Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+    good: Good,
+) -> u32 {
+    if good.is_food() {
+        granary_q
+            .iter()
+            .map(|(_, storage)| storage.amount(good))
+            .sum()
+    } else {
+        construction
+            .resources
+            .goods
+            .get(&good)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/**
+* Adds up to `amount` of `good` where the city keeps it, returning how much found room
+*/
+fn bring_in(
+    construction: &mut Construction,
+    granary_q: &mut Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+    good: Good,
+    amount: u32,
+) -> u32 {
+    if !good.is_food() {
+        *construction.resources.goods.entry(good).or_insert(0) += amount;
+        return amount;
+    }
+    let mut stored = 0;
+    for (building_type, mut storage) in granary_q {
+        if *building_type == BuildingType::Granary {
+            stored += storage.store(good, amount - stored, building_type.storage_capacity());
+        }
+    }
+    stored
+}
+
+/**
+* Takes up to `amount` of `good` out of the city, returning how much there was
+*/
+fn send_out(
+    construction: &mut Construction,
+    granary_q: &mut Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+    good: Good,
+    amount: u32,
+) -> u32 {
+    if !good.is_food() {
+        let kept = construction.resources.goods.entry(good).or_insert(0);
+        let taken = amount.min(*kept);
+        *kept -= taken;
+        return taken;
+    }
+    let mut taken = 0;
+    for (building_type, mut storage) in granary_q {
+        if *building_type == BuildingType::Granary {
+            taken += storage.take(good, amount - taken);
+        }
+    }
+    taken
+}
+
+/**
+* Gold paid for `amount` goods at `price` each, capped where it no longer fits
+*/
+fn gold_for(amount: u32, price: u32) -> i32 {
+    i32::try_from(u64::from(amount) * u64::from(price)).unwrap_or(i32::MAX)
+}
+
+/**
+* Brings the traders of every partner due this month to the city's trade post
+* or dock, where they buy and sell what the trade settings allow
+*/
+pub(super) fn visit_traders(
+    mut new_month: EventReader<NewMonth>,
+    mut construction: Construction,
+    mut ledger: ResMut<Ledger>,
+    settings: Res<TradeSettings>,
+    mut prices: ResMut<MarketPrices>,
+    post_q: Query<(&BuildingType, &AnchorTile), Finished>,
+    mut granary_q: Query<(&BuildingType, &mut Storage), Without<UnderConstruction>>,
+) {
+    for event in new_month.read() {
+        let Some(partners) = construction
+            .levels
+            .get(construction.current_level.0.id())
+            .map(|level: &Level| level.trade_partners.clone())
+        else {
+            continue;
+        };
+
+        for partner in partners {
+            if event.month % partner.every.max(1) != 0 {
+                continue;
+            }
+            let Some((_, post)) = post_q
+                .iter()
+                .find(|(building_type, _)| **building_type == partner.route.building())
+            else {
+                continue;
+            };
+
+            let mut room = partner.capacity;
+            for (good, setting) in settings.0.iter() {
+                let stock = in_stock(&construction, &granary_q, *good);
+                match setting {
+                    TradeSetting::Export(keep) => {
+                        let Some(usual) = partner.buys.get(good) else {
+                            continue;
+                        };
+                        let price = prices.price(*good, *usual);
+                        let wanted = stock.saturating_sub(*keep).min(room);
+                        let sold = send_out(&mut construction, &mut granary_q, *good, wanted);
+                        let income = gold_for(sold, price);
+                        construction.resources.gold =
+                            construction.resources.gold.saturating_add(income);
+                        ledger.book(event.month, LedgerEntry::Exports, income);
+                        prices.trade(*good, -(sold as i32));
+                        room -= sold;
+                    }
+                    TradeSetting::Import(up_to) => {
+                        let Some(usual) = partner.sells.get(good) else {
+                            continue;
+                        };
+                        let price = prices.price(*good, *usual);
+                        let affordable = construction.resources.gold.max(0) as u32 / price;
+                        let wanted = up_to.saturating_sub(stock).min(room).min(affordable);
+                        let bought = bring_in(&mut construction, &mut granary_q, *good, wanted);
+                        let expense = gold_for(bought, price);
+                        construction.resources.gold =
+                            construction.resources.gold.saturating_sub(expense);
+                        ledger.book(event.month, LedgerEntry::Imports, -expense);
+                        prices.trade(*good, bought as i32);
+                        room -= bought;
+                    }
+                    TradeSetting::None => {}
+                }
+            }
+
+            let translation = construction.walker_translation(post.0).unwrap_or_default();
+            construction.commands.spawn((
+                Trader {
+                    tile: post.0,
+                    months_left: TRADER_STAY_MONTHS,
+                },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: partner.route.color(),
+                        custom_size: Some(Vec2::new(20., 14.)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                },
+            ));
+            info!(
+                "Traders from {} traded {} goods",
+                partner.name,
+                partner.capacity - room
+            );
+        }
+    }
+}
+
+/**
+* Sends traders back home once they're done
+*/
+pub fn leave_traders(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    mut commands: Commands,
+    mut trader_q: Query<(Entity, &mut Trader)>,
+) {
+    let months = time.delta_seconds() / game_timer.0.duration().as_secs_f32();
+
+    for (entity, mut trader) in &mut trader_q {
+        trader.months_left -= months;
+        if trader.months_left <= 0. {
+            commands.entity(entity).despawn();
+        }
+    }
+}
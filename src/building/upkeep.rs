@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use super::components::BuildingType;
+use super::site::{Finished, UnderConstruction};
+use super::Construction;
+use crate::command::PlayerCommand;
+use crate::resources::{GlobalResources, Ledger, LedgerEntry};
+use crate::time::{GameTimer, NewMonth};
+
+/// Condition under which a building starts working less
+const GOOD_CONDITION: f32 = 0.5;
+
+/// State of repair of a building, from 0 (crumbling) to 1 (like new)
+#[derive(Component, Debug, Clone)]
+pub struct Condition(pub f32);
+
+impl Default for Condition {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+impl Condition {
+    /**
+     * Share of its full output the building still manages in this condition
+     */
+    pub fn efficiency(&self) -> f32 {
+        (self.0 / GOOD_CONDITION).clamp(0., 1.)
+    }
+
+    /**
+     * Gold it takes to bring a `building_type` in this condition back to new
+     */
+    pub fn repair_cost(&self, building_type: BuildingType) -> u32 {
+        (building_type.cost() as f32 * (1. - self.0)).ceil() as u32
+    }
+}
+
+/**
+* Wears down every working building
+*/
+pub fn decay_condition(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    mut building_q: Query<(&BuildingType, &mut Condition), Finished>,
+) {
+    let months = time.delta_seconds() / game_timer.0.duration().as_secs_f32();
+
+    for (building_type, mut condition) in &mut building_q {
+        condition.0 = (condition.0 - building_type.decay() * months).max(0.);
+    }
+}
+
+pub fn pay_upkeep(
+    mut new_month: EventReader<NewMonth>,
+    mut resources: ResMut<GlobalResources>,
+    mut ledger: ResMut<Ledger>,
+    building_q: Query<&BuildingType, Finished>,
+) {
+    for event in new_month.read() {
+        let upkeep: i32 = building_q
+            .iter()
+            .map(|building| building.upkeep() as i32)
+            .sum();
+        resources.gold -= upkeep;
+        ledger.book(event.month, LedgerEntry::Upkeep, -upkeep);
+    }
+}
+
+pub(super) fn apply_repairs(
+    mut player_commands: EventReader<PlayerCommand>,
+    mut construction: Construction,
+    mut condition_q: Query<&mut Condition, Without<UnderConstruction>>,
+) {
+    for player_command in player_commands.read() {
+        let PlayerCommand::Repair { x, y } = *player_command else {
+            continue;
+        };
+
+        let repaired = construction
+            .building_at(TilePos { x, y })
+            .and_then(|building| {
+                let (building_type, _, _, _) = construction.building_q.get(building).ok()?;
+                let mut condition = condition_q.get_mut(building).ok()?;
+                let cost = condition.repair_cost(*building_type) as i32;
+                if construction.resources.gold < cost {
+                    return None;
+                }
+                construction.resources.gold -= cost;
+                condition.0 = 1.;
+                Some(())
+            });
+
+        if repaired.is_none() {
+            warn!("Cannot apply {:?}", player_command);
+        }
+    }
+}
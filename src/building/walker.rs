@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use super::components::{AnchorTile, Building, BuildingType};
+use super::risk::{OnFire, Risk};
+use super::site::UnderConstruction;
+use super::Construction;
+use crate::time::GameTimer;
+
+/// Tiles a walker crosses in a month
+const WALKER_TILES_PER_MONTH: f32 = 30.;
+/// Tiles a walker crosses before heading home and a new one sets out
+const WALKER_RANGE: u32 = 40;
+/// How far from its tile a walker looks after buildings
+const SERVICE_RADIUS: u32 = 2;
+
+/// What a walker does for the buildings it passes by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkerKind {
+    /// Puts out fires and resets the fire risk
+    Prefect,
+    /// Repairs damage and resets the collapse risk
+    Engineer,
+}
+
+impl WalkerKind {
+    fn color(&self) -> Color {
+        match self {
+            WalkerKind::Prefect => Color::srgb(0.8, 0.1, 0.1),
+            WalkerKind::Engineer => Color::srgb(0.2, 0.3, 0.9),
+        }
+    }
+}
+
+/// Someone wandering the map from a service building
+#[derive(Component, Debug)]
+pub struct Walker {
+    pub kind: WalkerKind,
+    pub home: Entity,
+    pub position: TilePos,
+    previous: TilePos,
+    steps_left: u32,
+    /// Part of the way to the next tile, from 0 to 1
+    progress: f32,
+}
+
+/// Where walkers choose their way, kept as a resource so replays make the same choices.
+#[derive(Resource, Debug)]
+pub struct WalkerRng(pub u64);
+
+impl Default for WalkerRng {
+    fn default() -> Self {
+        Self(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl WalkerRng {
+    /**
+     * Next number below `bound`, from a xorshift sequence
+     */
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/**
+* Sends a walker out of every working service building that has none
+*/
+#[allow(clippy::type_complexity)]
+pub(super) fn spawn_walkers(
+    mut construction: Construction,
+    home_q: Query<
+        (Entity, &BuildingType, &AnchorTile),
+        (With<Building>, Without<UnderConstruction>, Without<OnFire>),
+    >,
+    walker_q: Query<&Walker>,
+) {
+    for (home, building_type, anchor) in &home_q {
+        let Some(kind) = building_type.walker() else {
+            continue;
+        };
+        if walker_q.iter().any(|walker| walker.home == home) {
+            continue;
+        }
+
+        let translation = construction
+            .walker_translation(anchor.0)
+            .unwrap_or_default();
+        construction.commands.spawn((
+            Walker {
+                kind,
+                home,
+                position: anchor.0,
+                previous: anchor.0,
+                steps_left: WALKER_RANGE,
+                progress: 0.,
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: kind.color(),
+                    custom_size: Some(Vec2::new(8., 16.)),
+                    ..default()
+                },
+                transform: Transform::from_translation(translation),
+                ..default()
+            },
+        ));
+    }
+}
+
+/**
+* Walks every walker across the land, looking after the buildings
+* around it on each tile
+*/
+pub(super) fn move_walkers(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    mut rng: ResMut<WalkerRng>,
+    mut construction: Construction,
+    mut walker_q: Query<(Entity, &mut Walker, &mut Transform)>,
+    mut risk_q: Query<&mut Risk>,
+) {
+    let months = time.delta_seconds() / game_timer.0.duration().as_secs_f32();
+
+    for (entity, mut walker, mut transform) in &mut walker_q {
+        if construction.building_q.get(walker.home).is_err() {
+            construction.commands.entity(entity).despawn();
+            continue;
+        }
+
+        walker.progress += months * WALKER_TILES_PER_MONTH;
+        while walker.progress >= 1. && walker.steps_left > 0 {
+            walker.progress -= 1.;
+            walker.steps_left -= 1;
+
+            let ways = construction.walkable_neighbours(walker.position);
+            let onward: Vec<_> = ways
+                .iter()
+                .copied()
+                .filter(|pos| *pos != walker.previous)
+                .collect();
+            let ways = if onward.is_empty() { ways } else { onward };
+            if ways.is_empty() {
+                continue;
+            }
+            let next = ways[rng.below(ways.len())];
+            walker.previous = walker.position;
+            walker.position = next;
+
+            for building in construction.buildings_around(&[next], SERVICE_RADIUS) {
+                let Ok(mut risk) = risk_q.get_mut(building) else {
+                    continue;
+                };
+                match walker.kind {
+                    WalkerKind::Prefect => {
+                        risk.fire = 0.;
+                        construction.commands.entity(building).remove::<OnFire>();
+                    }
+                    WalkerKind::Engineer => risk.damage = 0.,
+                }
+            }
+        }
+
+        if let Some(translation) = construction.walker_translation(walker.position) {
+            transform.translation = translation;
+        }
+        if walker.steps_left == 0 {
+            construction.commands.entity(entity).despawn();
+        }
+    }
+}
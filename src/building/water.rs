@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use super::components::{Building, BuildingType};
+use super::Construction;
+use crate::time::NewMonth;
+
+/// Set when buildings went up, came down or changed since the water network was
+/// last worked out, cleared once it's worked out again
+#[derive(Resource, Default)]
+pub struct WaterNetworkDirty(pub bool);
+
+/// A reservoir, aqueduct or fountain that water reaches from a working reservoir
+#[derive(Component)]
+pub struct WaterConnected;
+
+/// A house within range of a connected fountain
+#[derive(Component)]
+pub struct WaterCoverage;
+
+/**
+* Follows aqueducts out of every finished reservoir to find which water buildings
+* are connected, then which houses the connected fountains reach. Only done at the
+* start of a month, or when `WaterNetworkDirty` says buildings changed
+*/
+pub(super) fn update_water_network(
+    mut construction: Construction,
+    building_q: Query<(Entity, Has<WaterConnected>, Has<WaterCoverage>), With<Building>>,
+    mut new_month: EventReader<NewMonth>,
+) {
+    let month_started = new_month.read().count() > 0;
+    if !(month_started || construction.water_dirty.0) {
+        return;
+    }
+    construction.water_dirty.0 = false;
+
+    let finished_water_building = |construction: &Construction, entity: Entity| {
+        construction
+            .building_q
+            .get(entity)
+            .is_ok_and(|(building_type, _, _, site)| {
+                building_type.carries_water() && site.is_none()
+            })
+    };
+
+    // In the order they were reached, each listed once
+    let mut connected: Vec<Entity> = building_q
+        .iter()
+        .map(|(entity, _, _)| entity)
+        .filter(|entity| {
+            construction
+                .building_q
+                .get(*entity)
+                .is_ok_and(|(building_type, _, _, site)| {
+                    *building_type == BuildingType::Reservoir && site.is_none()
+                })
+        })
+        .collect();
+
+    let mut reached: HashSet<Entity> = connected.iter().copied().collect();
+
+    let mut next = 0;
+    while next < connected.len() {
+        let area = construction.area(connected[next]);
+        for neighbour in construction.buildings_next_to(&area) {
+            if !reached.contains(&neighbour) && finished_water_building(&construction, neighbour) {
+                reached.insert(neighbour);
+                connected.push(neighbour);
+            }
+        }
+        next += 1;
+    }
+
+    let mut covered = HashSet::new();
+    for entity in &connected {
+        let Ok((building_type, _, _, _)) = construction.building_q.get(*entity) else {
+            continue;
+        };
+        let Some(range) = building_type.water_range() else {
+            continue;
+        };
+        let area = construction.area(*entity);
+        for building in construction.buildings_around(&area, range) {
+            let is_house =
+                construction
+                    .building_q
+                    .get(building)
+                    .is_ok_and(|(building_type, _, _, site)| {
+                        *building_type == BuildingType::House && site.is_none()
+                    });
+            if is_house {
+                covered.insert(building);
+            }
+        }
+    }
+
+    for (entity, was_connected, was_covered) in &building_q {
+        let mut building = construction.commands.entity(entity);
+        match (reached.contains(&entity), was_connected) {
+            (true, false) => {
+                building.insert(WaterConnected);
+            }
+            (false, true) => {
+                building.remove::<WaterConnected>();
+            }
+            _ => {}
+        }
+        match (covered.contains(&entity), was_covered) {
+            (true, false) => {
+                building.insert(WaterCoverage);
+            }
+            (false, true) => {
+                building.remove::<WaterCoverage>();
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,10 +1,142 @@
+use std::collections::BTreeMap;
+
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::EguiContexts;
+use serde::{Deserialize, Serialize};
+
+use crate::building::walker::Walker;
+use crate::cursor::{HoveredBuilding, SelectedTile};
+use crate::grid::{TILE_H, TILE_W};
+use crate::view::ViewRotation;
+use crate::AppState;
+
+/// World units per second the camera pans at, at a scale of 1
+const PAN_SPEED: f32 = 500.;
+/// Pixels from the window border where the cursor scrolls the view
+const EDGE_SCROLL_MARGIN: f32 = 16.;
+/// How many times closer holding Z or X zooms per second
+const KEY_ZOOM_RATE: f32 = 2.;
+/// How many times closer one notch of the mouse wheel zooms
+const WHEEL_ZOOM_STEP: f32 = 1.2;
+/// Pixels of touchpad scrolling that count as one wheel notch
+const PIXELS_PER_NOTCH: f32 = 50.;
+/// How fast the scale catches up with the zoom target, higher is snappier
+const ZOOM_SMOOTHING: f32 = 12.;
+const MIN_SCALE: f32 = 0.5;
+/// Tiles of empty space the view can show past the edges of the map
+const BOUNDS_MARGIN: f32 = 2.;
+/// Number keys going back to camera bookmarks, or storing them with Ctrl held
+const BOOKMARK_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
 
 pub struct CameraPlugin;
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<CameraZoom>();
+        app.init_resource::<CameraBookmarks>();
+        app.init_resource::<CameraFollow>();
         app.add_systems(Startup, setup_camera);
-        app.add_systems(Update, movement);
+        app.add_systems(OnEnter(AppState::Level), center_on_level);
+        app.add_systems(
+            Update,
+            (
+                bookmarks,
+                pick_follow_target.run_if(resource_exists::<SelectedTile>),
+                movement,
+                edge_scroll,
+                drag_pan,
+                wheel_zoom,
+                smooth_zoom,
+                follow_target,
+                clamp_to_bounds.run_if(resource_exists::<CameraBounds>),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// The scale the camera is zooming to, eased towards by `smooth_zoom`
+#[derive(Resource)]
+pub struct CameraZoom {
+    pub target: f32,
+    /// Point of the window that stays still while zooming, from its centre with y up
+    focus: Vec2,
+}
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        Self {
+            target: 1.,
+            focus: Vec2::ZERO,
+        }
+    }
+}
+
+impl CameraZoom {
+    fn zoom_by(&mut self, factor: f32, focus: Vec2) {
+        self.target = (self.target * factor).max(MIN_SCALE);
+        self.focus = focus;
+    }
+}
+
+/// A camera position and zoom stored under a number key
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    /// Where the camera looks, as the simulation lays out the map
+    pub x: f32,
+    pub y: f32,
+    pub scale: f32,
+}
+
+/// Camera bookmarks by number key, kept in the recorded game
+#[derive(Resource, Default, Debug, Clone)]
+pub struct CameraBookmarks(pub BTreeMap<u8, CameraBookmark>);
+
+/// The walker or building the camera stays on, until the player pans away
+#[derive(Resource, Default, Debug)]
+pub struct CameraFollow(pub Option<Entity>);
+
+/// The diamond the camera stays within, around the loaded level
+#[derive(Resource, Debug)]
+pub struct CameraBounds {
+    pub centre: Vec2,
+    /// Distance from the centre to the left and right corners, then the top and bottom ones
+    pub radius: Vec2,
+}
+
+impl CameraBounds {
+    /**
+     * The point of the diamond closest to `point` along the way to its centre
+     */
+    pub fn clamp(&self, point: Vec2) -> Vec2 {
+        let offset = point - self.centre;
+        let distance = offset.x.abs() / self.radius.x + offset.y.abs() / self.radius.y;
+        if distance <= 1. {
+            point
+        } else {
+            self.centre + offset / distance
+        }
+    }
+
+    /**
+     * Scale at which the whole diamond, drawn turned by `view`, fits in a `window`-sized view
+     */
+    pub fn fitting_scale(&self, window: Vec2, view: ViewRotation) -> f32 {
+        let matrix = view.matrix();
+        let radius = Mat2::from_cols(matrix.x_axis.abs(), matrix.y_axis.abs()) * self.radius;
+        (2. * radius / window).max_element().max(MIN_SCALE)
     }
 }
 
@@ -12,16 +144,146 @@ fn setup_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
 
-use bevy::{input::ButtonInput, math::Vec3, render::camera::Camera};
+/**
+* Works out the bounds of the loaded map and moves the camera to its centre
+*/
+fn center_on_level(
+    mut commands: Commands,
+    tilemap_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType, &Transform), Without<Camera>>,
+    mut camera_q: Query<&mut Transform, With<Camera>>,
+) {
+    let Ok((map_size, grid_size, map_type, map_transform)) = tilemap_q.get_single() else {
+        return;
+    };
+
+    let corners: Vec<Vec2> = [
+        (0, 0),
+        (map_size.x - 1, 0),
+        (0, map_size.y - 1),
+        (map_size.x - 1, map_size.y - 1),
+    ]
+    .into_iter()
+    .map(|(x, y)| {
+        let centre = TilePos { x, y }.center_in_world(grid_size, map_type);
+        map_transform.transform_point(centre.extend(0.)).truncate()
+    })
+    .collect();
+    let centre = corners.iter().sum::<Vec2>() / corners.len() as f32;
+    let reach = corners
+        .iter()
+        .map(|corner| (*corner - centre).abs())
+        .fold(Vec2::ZERO, Vec2::max);
+    let radius = reach + (0.5 + BOUNDS_MARGIN) * Vec2::new(TILE_W as f32, TILE_H as f32);
+
+    for mut transform in &mut camera_q {
+        transform.translation.x = centre.x;
+        transform.translation.y = centre.y;
+    }
+    commands.insert_resource(CameraBounds { centre, radius });
+}
+
+/**
+* Cursor position from the centre of the window with y up, if it's in the window
+*/
+fn cursor_from_centre(window: &Window) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    Some(Vec2::new(
+        cursor.x - window.width() / 2.,
+        window.height() / 2. - cursor.y,
+    ))
+}
+
+/**
+* Stores the camera position and zoom under a number key pressed with Ctrl,
+* and goes back to them when the number key is pressed alone
+*/
+fn bookmarks(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: Res<ViewRotation>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut zoom: ResMut<CameraZoom>,
+    mut follow: ResMut<CameraFollow>,
+    mut query: Query<&mut Transform, With<Camera>>,
+) {
+    let Some(number) = (1..)
+        .zip(BOOKMARK_KEYS)
+        .find(|(_, key)| keys.just_pressed(*key))
+        .map(|(number, _)| number)
+    else {
+        return;
+    };
+    let storing = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    for mut transform in &mut query {
+        if storing {
+            // Stored as the simulation lays out the map, so they survive turning the view
+            let spot = view.unproject(transform.translation.truncate());
+            bookmarks.0.insert(
+                number,
+                CameraBookmark {
+                    x: spot.x,
+                    y: spot.y,
+                    scale: zoom.target,
+                },
+            );
+            info!("Stored camera bookmark {}", number);
+        } else if let Some(bookmark) = bookmarks.0.get(&number) {
+            let drawn = view.project(Vec2::new(bookmark.x, bookmark.y));
+            transform.translation.x = drawn.x;
+            transform.translation.y = drawn.y;
+            zoom.target = bookmark.scale;
+            zoom.focus = Vec2::ZERO;
+            follow.0 = None;
+        }
+    }
+}
+
+/**
+* Locks the camera on the walker under the cursor with Ctrl+F, or else on the
+* building drawn there, and lets go again on the next Ctrl+F
+*/
+fn pick_follow_target(
+    keys: Res<ButtonInput<KeyCode>>,
+    selected_tile: Res<SelectedTile>,
+    hovered_building: Res<HoveredBuilding>,
+    mut follow: ResMut<CameraFollow>,
+    tile_q: Query<&TilePos>,
+    walker_q: Query<(Entity, &Walker)>,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || !keys.just_pressed(KeyCode::KeyF)
+    {
+        return;
+    }
+    if follow.0.take().is_some() {
+        return;
+    }
+    let pos = selected_tile.0.and_then(|tile| tile_q.get(tile).ok());
 
-// A simple camera system for moving and zooming the camera.
-#[allow(dead_code)]
+    let walker = walker_q
+        .iter()
+        .find(|(_, walker)| Some(&walker.position) == pos)
+        .map(|(entity, _)| entity);
+    follow.0 = walker.or(hovered_building.0);
+}
+
+/**
+* Pans with WASD, faster when zoomed out, and zooms towards the centre with Z and X,
+* unless Ctrl is held. Panning lets go of whatever the camera follows
+*/
 pub fn movement(
     time: Res<Time>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    mut zoom: ResMut<CameraZoom>,
+    mut follow: ResMut<CameraFollow>,
+    mut query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
 ) {
-    for (mut transform, mut ortho) in &mut query {
+    // Leave shortcuts like Ctrl+Z to `undo_redo`
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    for (mut transform, ortho) in &mut query {
         let mut direction = Vec3::ZERO;
 
         if keyboard_input.pressed(KeyCode::KeyA) {
@@ -41,21 +303,192 @@ pub fn movement(
         }
 
         if keyboard_input.pressed(KeyCode::KeyZ) {
-            ortho.scale += 0.1;
+            zoom.zoom_by(KEY_ZOOM_RATE.powf(time.delta_seconds()), Vec2::ZERO);
         }
 
         if keyboard_input.pressed(KeyCode::KeyX) {
-            ortho.scale -= 0.1;
+            zoom.zoom_by(KEY_ZOOM_RATE.powf(-time.delta_seconds()), Vec2::ZERO);
         }
 
-        if ortho.scale < 0.5 {
-            ortho.scale = 0.5;
+        if direction != Vec3::ZERO {
+            follow.0 = None;
         }
 
         let z = transform.translation.z;
-        transform.translation += time.delta_seconds() * direction * 500.;
+        transform.translation += time.delta_seconds() * direction * PAN_SPEED * ortho.scale;
         // Important! We need to restore the Z values when moving the camera around.
         // Bevy has a specific camera setup and this can mess with how our layers are shown.
         transform.translation.z = z;
     }
 }
+
+/**
+* Pans towards whichever border of the window the cursor rests on, unless it
+* rests on a window of the interface, letting go of whatever the camera follows
+*/
+fn edge_scroll(
+    time: Res<Time>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut contexts: EguiContexts,
+    mut follow: ResMut<CameraFollow>,
+    mut query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    if !window.focused || contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let Some(cursor) = cursor_from_centre(window) else {
+        return;
+    };
+
+    let half = Vec2::new(window.width(), window.height()) / 2. - EDGE_SCROLL_MARGIN;
+    let direction = Vec2::new(
+        if cursor.x > half.x {
+            1.
+        } else if cursor.x < -half.x {
+            -1.
+        } else {
+            0.
+        },
+        if cursor.y > half.y {
+            1.
+        } else if cursor.y < -half.y {
+            -1.
+        } else {
+            0.
+        },
+    );
+    if direction != Vec2::ZERO {
+        follow.0 = None;
+    }
+
+    for (mut transform, ortho) in &mut query {
+        let pan = direction * time.delta_seconds() * PAN_SPEED * ortho.scale;
+        transform.translation += pan.extend(0.);
+    }
+}
+
+/**
+* Drags the map along with the mouse while the middle button is held,
+* letting go of whatever the camera follows
+*/
+fn drag_pan(
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut contexts: EguiContexts,
+    mut follow: ResMut<CameraFollow>,
+    mut query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let delta: Vec2 = motion.read().map(|event| event.delta).sum();
+    if !mouse_buttons.pressed(MouseButton::Middle) || contexts.ctx_mut().is_using_pointer() {
+        return;
+    }
+    if delta != Vec2::ZERO {
+        follow.0 = None;
+    }
+
+    for (mut transform, ortho) in &mut query {
+        transform.translation.x -= delta.x * ortho.scale;
+        transform.translation.y += delta.y * ortho.scale;
+    }
+}
+
+/**
+* Zooms towards the point under the cursor with the mouse wheel
+*/
+fn wheel_zoom(
+    mut wheel: EventReader<MouseWheel>,
+    mut contexts: EguiContexts,
+    mut zoom: ResMut<CameraZoom>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+) {
+    let notches: f32 = wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+        })
+        .sum();
+    if notches == 0. || contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+
+    let focus = window_q
+        .get_single()
+        .ok()
+        .and_then(cursor_from_centre)
+        .unwrap_or_default();
+    zoom.zoom_by(WHEEL_ZOOM_STEP.powf(-notches), focus);
+}
+
+/**
+* Eases the camera scale towards the zoom target, keeping the world point
+* under the zoom focus in place
+*/
+fn smooth_zoom(
+    time: Res<Time>,
+    zoom: Res<CameraZoom>,
+    mut query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let keep = (-ZOOM_SMOOTHING * time.delta_seconds()).exp();
+
+    for (mut transform, mut ortho) in &mut query {
+        let scale = zoom.target + (ortho.scale - zoom.target) * keep;
+        // The focus sits at `focus * scale` from the camera in the world
+        let shift = zoom.focus * (ortho.scale - scale);
+        transform.translation += shift.extend(0.);
+        ortho.scale = scale;
+    }
+}
+
+/**
+* Keeps the camera centred on what it follows, as it was last drawn,
+* letting go once it's gone
+*/
+fn follow_target(
+    mut follow: ResMut<CameraFollow>,
+    target_q: Query<&GlobalTransform>,
+    mut query: Query<&mut Transform, With<Camera>>,
+) {
+    let Some(target) = follow.0 else {
+        return;
+    };
+    let Ok(target_transform) = target_q.get(target) else {
+        follow.0 = None;
+        return;
+    };
+
+    let drawn = target_transform.translation();
+    for mut transform in &mut query {
+        transform.translation.x = drawn.x;
+        transform.translation.y = drawn.y;
+    }
+}
+
+/**
+* Keeps the camera over the map and stops zooming out once it all fits in view
+*/
+fn clamp_to_bounds(
+    bounds: Res<CameraBounds>,
+    view: Res<ViewRotation>,
+    mut zoom: ResMut<CameraZoom>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let max_scale = window_q
+        .get_single()
+        .map(|window| bounds.fitting_scale(Vec2::new(window.width(), window.height()), *view))
+        .unwrap_or(f32::MAX);
+    zoom.target = zoom.target.min(max_scale);
+
+    for (mut transform, mut ortho) in &mut query {
+        ortho.scale = ortho.scale.min(max_scale);
+        // The bounds are laid out like the simulation, not like the rotated view
+        let spot = bounds.clamp(view.unproject(transform.translation.truncate()));
+        let clamped = view.project(spot);
+        transform.translation.x = clamped.x;
+        transform.translation.y = clamped.y;
+    }
+}
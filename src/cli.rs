@@ -1,7 +1,7 @@
 use bevy::prelude::Resource;
 use clap::Parser;
 
-#[derive(Parser, Debug, Resource)]
+#[derive(Parser, Debug, Resource, Default)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Map file to load
@@ -10,4 +10,22 @@ pub struct Args {
     /// Map size in tiles
     #[arg(short, long, default_value = "32")]
     pub tilemap_size: Option<u32>,
+    /// Run the simulation without a window and print a JSON summary
+    #[arg(long)]
+    pub headless: bool,
+    /// In-game months to simulate in headless mode
+    #[arg(long, default_value = "12")]
+    pub months: u32,
+    /// JSON file with build commands to apply in headless mode
+    #[arg(long)]
+    pub script: Option<String>,
+    /// Record every player command into this replay file
+    #[arg(long)]
+    pub record: Option<String>,
+    /// Play back a replay file without a window and verify its final state
+    #[arg(long)]
+    pub replay: Option<String>,
+    /// Play back a replay file in the window, with its camera bookmarks, and keep playing
+    #[arg(long)]
+    pub load: Option<String>,
 }
@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+
+use crate::building::components::{AnchorTile, Building, BuildingType, Orientation};
+use crate::building::farming::Field;
+use crate::building::housing::Household;
+use crate::building::prices::MarketPrices;
+use crate::building::risk::{OnFire, Risk, Rubble};
+use crate::building::site::UnderConstruction;
+use crate::building::storage::Storage;
+use crate::building::trade::{TradeSetting, TradeSettings};
+use crate::building::upkeep::Condition;
+use crate::building::walker::WalkerRng;
+use crate::camera::{CameraBookmark, CameraBookmarks};
+use crate::cli::Args;
+use crate::grid::{CurrentLevel, Occupied};
+use crate::resources::{GlobalResources, Good};
+use crate::time::{Calendar, TimeSpeed};
+use crate::AppState;
+
+pub struct CommandPlugin;
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerCommand>();
+        app.init_resource::<SimulationTick>();
+        app.init_resource::<ReplayRecorder>();
+        app.init_resource::<ReplayPlayback>();
+
+        app.configure_sets(
+            FixedUpdate,
+            (CommandSet::Issue, CommandSet::Apply)
+                .chain()
+                .run_if(in_state(AppState::Level)),
+        );
+
+        app.add_systems(Startup, setup_recorder);
+        app.add_systems(
+            FixedUpdate,
+            (
+                (advance_tick, play_back_commands)
+                    .chain()
+                    .in_set(CommandSet::Issue),
+                record_commands.in_set(CommandSet::Apply),
+            ),
+        );
+        app.add_systems(Last, save_replay_on_exit);
+    }
+}
+
+/// Every change the player can make to the simulation.
+///
+/// Input and UI systems only send these; the simulation applies them in
+/// `FixedUpdate` so that a recorded game can be replayed tick by tick.
+#[derive(Event, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PlayerCommand {
+    PlaceBuilding {
+        building_type: BuildingType,
+        x: u32,
+        y: u32,
+        #[serde(default)]
+        orientation: Orientation,
+    },
+    Demolish {
+        x: u32,
+        y: u32,
+    },
+    /// Restores the condition of the building covering the tile, for gold
+    Repair {
+        x: u32,
+        y: u32,
+    },
+    /// Turns the building covering the tile into its upgrade
+    Upgrade {
+        x: u32,
+        y: u32,
+    },
+    /// Takes back the last construction or demolition of the current month
+    Undo,
+    Redo,
+    /// Changes what the city imports or exports of a good
+    SetTrade {
+        good: Good,
+        setting: TradeSetting,
+    },
+    SetTaxRate(u32),
+    SetSpeed(TimeSpeed),
+    Pause,
+}
+
+/// Systems issuing player commands run in `Issue`, systems handling them in `Apply`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CommandSet {
+    Issue,
+    Apply,
+}
+
+/// Number of `FixedUpdate` steps run since the level was loaded.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct SimulationTick(pub u64);
+
+fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    pub tick: u64,
+    pub command: PlayerCommand,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub level: Option<String>,
+    pub commands: Vec<RecordedCommand>,
+    pub final_tick: u64,
+    pub state_hash: u64,
+    /// Camera bookmarks the player stored, put back when the replay is played
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub camera_bookmarks: BTreeMap<u8, CameraBookmark>,
+}
+
+impl Replay {
+    pub fn from_file(path: &str) -> Self {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Cannot read replay {path}: {e}"));
+        serde_json::from_str(&contents).unwrap_or_else(|e| panic!("Invalid replay {path}: {e}"))
+    }
+
+    /// Its commands, waiting to be played back
+    pub fn playback(&self) -> ReplayPlayback {
+        ReplayPlayback(self.commands.iter().cloned().collect())
+    }
+}
+
+/// Commands applied so far, written to `path` when the game exits.
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    pub path: Option<String>,
+    pub commands: Vec<RecordedCommand>,
+}
+
+impl ReplayRecorder {
+    pub fn save(world: &mut World) {
+        let Some(path) = world.resource::<ReplayRecorder>().path.clone() else {
+            return;
+        };
+
+        let replay = Replay {
+            level: world
+                .resource::<CurrentLevel>()
+                .0
+                .path()
+                .map(|path| path.to_string()),
+            commands: world.resource::<ReplayRecorder>().commands.clone(),
+            final_tick: world.resource::<SimulationTick>().0,
+            state_hash: state_hash(world),
+            camera_bookmarks: world
+                .get_resource::<CameraBookmarks>()
+                .map(|bookmarks| bookmarks.0.clone())
+                .unwrap_or_default(),
+        };
+
+        match std::fs::write(&path, serde_json::to_string_pretty(&replay).unwrap()) {
+            Ok(()) => info!("Replay saved to {path}"),
+            Err(e) => error!("Cannot save replay to {path}: {e}"),
+        }
+    }
+}
+
+/// Recorded commands still waiting for their tick.
+#[derive(Resource, Default)]
+pub struct ReplayPlayback(pub VecDeque<RecordedCommand>);
+
+fn setup_recorder(args: Option<Res<Args>>, mut recorder: ResMut<ReplayRecorder>) {
+    recorder.path = args.and_then(|args| args.record.clone());
+}
+
+fn play_back_commands(
+    tick: Res<SimulationTick>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut commands: EventWriter<PlayerCommand>,
+) {
+    while playback.0.front().is_some_and(|c| c.tick <= tick.0) {
+        commands.send(playback.0.pop_front().unwrap().command);
+    }
+}
+
+fn record_commands(
+    tick: Res<SimulationTick>,
+    mut commands: EventReader<PlayerCommand>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    for command in commands.read() {
+        recorder.commands.push(RecordedCommand {
+            tick: tick.0,
+            command: command.clone(),
+        });
+    }
+}
+
+fn save_replay_on_exit(world: &mut World) {
+    if !world.resource::<Events<AppExit>>().is_empty() {
+        ReplayRecorder::save(world);
+    }
+}
+
+/// FNV-1a, so that hashes stored in replay files don't depend on the Rust version
+/// like `DefaultHasher` would.
+struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// What `state_hash` looks at in the building covering a tile
+type TileState = (
+    BuildingType,
+    Orientation,
+    Option<u32>,
+    (u32, u32),
+    Option<u32>,
+    u32,
+);
+
+/// Hash of everything the player's commands can affect, used to verify replays.
+pub fn state_hash(world: &mut World) -> u64 {
+    let mut hasher = StateHasher::default();
+
+    world.resource::<SimulationTick>().0.hash(&mut hasher);
+    world.resource::<Calendar>().month.hash(&mut hasher);
+    let resources = world.resource::<GlobalResources>();
+    resources.gold.hash(&mut hasher);
+    resources.tax_rate.hash(&mut hasher);
+    resources.goods.hash(&mut hasher);
+    world.resource::<WalkerRng>().0.hash(&mut hasher);
+    world.resource::<TradeSettings>().0.hash(&mut hasher);
+    world.resource::<MarketPrices>().state().hash(&mut hasher);
+
+    let mut building_q = world.query_filtered::<(
+        &BuildingType,
+        &Orientation,
+        Option<&UnderConstruction>,
+        &Risk,
+        Option<&OnFire>,
+        &Condition,
+    ), With<Building>>();
+    let mut rubble_q = world.query_filtered::<(), With<Rubble>>();
+    let mut tile_q = world.query::<(&TilePos, &Occupied)>();
+    // Construction progress, risks and condition are hashed by their bits, as they only need to match exactly.
+    let mut tiles: Vec<(u32, u32, Option<TileState>, bool)> = tile_q
+        .iter(world)
+        .map(|(pos, occupied)| {
+            let building = occupied
+                .0
+                .and_then(|entity| building_q.get(world, entity).ok())
+                .map(
+                    |(building_type, orientation, site, risk, fire, condition)| {
+                        let progress = site.map(|site| site.progress.to_bits());
+                        let risk = (risk.fire.to_bits(), risk.damage.to_bits());
+                        let fire = fire.map(|fire| fire.burnt.to_bits());
+                        let condition = condition.0.to_bits();
+                        (
+                            *building_type,
+                            *orientation,
+                            progress,
+                            risk,
+                            fire,
+                            condition,
+                        )
+                    },
+                );
+            let rubble = occupied
+                .0
+                .is_some_and(|entity| rubble_q.get(world, entity).is_ok());
+            (pos.x, pos.y, building, rubble)
+        })
+        .collect();
+    tiles.sort_by_key(|(x, y, _, _)| (*x, *y));
+    tiles.hash(&mut hasher);
+
+    // Fields, stored goods and households belong to whole buildings, so they're keyed by anchor.
+    let mut store_q = world.query_filtered::<(
+        &AnchorTile,
+        Option<&Field>,
+        &Storage,
+        Option<&Household>,
+    ), With<Building>>();
+    let mut stores: Vec<_> = store_q
+        .iter(world)
+        .map(|(anchor, field, storage, household)| {
+            let growth = field.map(|field| field.growth.to_bits());
+            let household = household.map(|household| {
+                (
+                    household.residents,
+                    household.level,
+                    household.variety,
+                    household.hungry_months,
+                )
+            });
+            (
+                anchor.0.x,
+                anchor.0.y,
+                growth,
+                storage.goods.clone(),
+                household,
+            )
+        })
+        .collect();
+    stores.sort_by_key(|(x, y, _, _, _)| (*x, *y));
+    stores.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_bookmarks_round_trip_through_a_replay_file() {
+        let bookmarks = BTreeMap::from([
+            (
+                1,
+                CameraBookmark {
+                    x: 120.,
+                    y: -48.5,
+                    scale: 0.75,
+                },
+            ),
+            (
+                9,
+                CameraBookmark {
+                    x: -16.,
+                    y: 300.,
+                    scale: 2.,
+                },
+            ),
+        ]);
+        let replay = Replay {
+            level: Some("test.level.json".to_string()),
+            commands: vec![RecordedCommand {
+                tick: 3,
+                command: PlayerCommand::Undo,
+            }],
+            final_tick: 64,
+            state_hash: 42,
+            camera_bookmarks: bookmarks.clone(),
+        };
+
+        let path = std::env::temp_dir().join(format!("bookmarks-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_string_pretty(&replay).unwrap()).unwrap();
+        let loaded = Replay::from_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.camera_bookmarks, bookmarks);
+        assert_eq!(loaded.playback().0.len(), 1);
+    }
+
+    #[test]
+    fn replays_without_bookmarks_leave_them_out() {
+        let replay = Replay {
+            level: None,
+            commands: vec![],
+            final_tick: 0,
+            state_hash: 0,
+            camera_bookmarks: BTreeMap::new(),
+        };
+        let json = serde_json::to_string(&replay).unwrap();
+        assert!(!json.contains("camera_bookmarks"));
+        let loaded: Replay = serde_json::from_str(&json).unwrap();
+        assert!(loaded.camera_bookmarks.is_empty());
+    }
+}
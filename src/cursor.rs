@@ -1,5 +1,16 @@
 use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
 use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::building::components::Building;
+use crate::grid::MapLayout;
+use crate::view::ViewRotation;
+
+/// Colour of the outline around the tile under the cursor
+const HOVER_OUTLINE: Color = Color::srgba(1., 1., 1., 0.8);
+/// Pixels of a building sprite fainter than this don't pick the building
+const PICK_ALPHA: u8 = 32;
 
 pub struct CursorPlugin;
 impl Plugin for CursorPlugin {
@@ -7,10 +18,21 @@ impl Plugin for CursorPlugin {
         app.init_resource::<CursorPos>();
         app.init_resource::<SelectedTile>();
         app.init_resource::<SelectedTilePos>();
-        app.add_systems(Update, (update_cursor_pos, hover_tile));
+        app.init_resource::<HoveredBuilding>();
+        app.add_systems(
+            Update,
+            (
+                update_cursor_pos,
+                hover_tile,
+                pick_building,
+                outline_hovered_tile,
+            )
+                .chain(),
+        );
     }
 }
 
+/// The tile under the cursor
 #[derive(Resource, Default)]
 pub struct SelectedTile(pub Option<Entity>);
 
@@ -23,9 +45,15 @@ impl Default for CursorPos {
     }
 }
 
+/// Position of the tile under the cursor
 #[derive(Resource)]
 #[derive(Default)]
-pub struct SelectedTilePos(Option<Vec2>);
+pub struct SelectedTilePos(pub Option<TilePos>);
+
+/// The building drawn under the cursor, which may stand on another tile
+/// than the one under it when its sprite is tall
+#[derive(Resource, Default)]
+pub struct HoveredBuilding(pub Option<Entity>);
 
 
 pub fn update_cursor_pos(
@@ -47,6 +75,7 @@ pub fn update_cursor_pos(
 
 fn hover_tile(
     cursor_pos: Res<CursorPos>,
+    view: Res<ViewRotation>,
     mut tilemap_q: Query<(
         &TilemapSize,
         &TilemapGridSize,
@@ -58,7 +87,8 @@ fn hover_tile(
     mut tile_pos_selected: ResMut<SelectedTilePos>,
 ) {
     for (map_size, grid_size, map_type, tile_storage, map_transform) in &mut tilemap_q {
-        let cursor_pos: Vec2 = cursor_pos.0;
+        // Picking works on the map as the simulation lays it out
+        let cursor_pos: Vec2 = view.unproject(cursor_pos.0);
 
         let cursor_in_map_pos: Vec2 = {
             // Extend the cursor_pos vec3 by 0.0 and 1.0
@@ -71,20 +101,117 @@ fn hover_tile(
             TilePos::from_world_pos(&cursor_in_map_pos, map_size, grid_size, map_type)
         {
             if let Some(tile_entity) = tile_storage.get(&tile_pos) {
-                tile_pos_selected.0 = Some(tile_pos.into());
+                tile_pos_selected.0 = Some(tile_pos);
                 tile_selected.0 = Some(tile_entity);
             } else {
                 tile_selected.0 = None;
                 tile_pos_selected.0 = None;
             }
+        } else {
+            tile_selected.0 = None;
+            tile_pos_selected.0 = None;
         }
     }
 }
 
-// fn print_terrain_state(selected_tile: Res<SelectedTile>, query: Query<&Terrain>) {
-//     if let Some(selected_tile) = selected_tile.0 {
-//         if let Ok(terrain) = query.get(selected_tile) {
-//             println!("Selected tile terrain: {:?}", terrain.terrain_type);
-//         }
-//     }
-// }
+/**
+* Finds the building whose sprite is drawn under the cursor, going by the
+* opaque pixels of the sprite rather than the tile under the cursor. Where
+* sprites overlap the one drawn in front wins. The pick holds while the
+* pointer is over a window
+*/
+fn pick_building(
+    cursor_pos: Res<CursorPos>,
+    images: Res<Assets<Image>>,
+    building_q: Query<(Entity, &GlobalTransform, &Sprite, &Handle<Image>), With<Building>>,
+    mut contexts: EguiContexts,
+    mut hovered: ResMut<HoveredBuilding>,
+) {
+    if contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+
+    let picked = building_q
+        .iter()
+        .filter(|(_, transform, sprite, texture)| {
+            sprite_covers(transform, sprite, images.get(*texture), cursor_pos.0)
+        })
+        .max_by(|(_, a, ..), (_, b, ..)| a.translation().z.total_cmp(&b.translation().z))
+        .map(|(entity, ..)| entity);
+    if hovered.0 != picked {
+        hovered.0 = picked;
+    }
+}
+
+/**
+* Whether the sprite draws an opaque pixel at `point`. A sprite whose image
+* hasn't loaded isn't drawn at all, one whose pixels can't be read counts
+* as opaque all over
+*/
+fn sprite_covers(
+    transform: &GlobalTransform,
+    sprite: &Sprite,
+    image: Option<&Image>,
+    point: Vec2,
+) -> bool {
+    let Some(image) = image else {
+        return false;
+    };
+    let size = sprite.custom_size.unwrap_or(image.size_f32());
+    let local = transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(transform.translation().z));
+
+    // From 0 at the bottom left corner of the sprite to 1 at the top right
+    let mut uv = local.truncate() / size + sprite.anchor.as_vec() + 0.5;
+    if !(0.0..1.0).contains(&uv.x) || !(0.0..1.0).contains(&uv.y) {
+        return false;
+    }
+    if sprite.flip_x {
+        uv.x = 1. - uv.x;
+    }
+    if sprite.flip_y {
+        uv.y = 1. - uv.y;
+    }
+    alpha_at(image, uv).is_none_or(|alpha| alpha >= PICK_ALPHA)
+}
+
+/// Alpha of the pixel at `uv`, if the image keeps its pixels as 8 bit RGBA
+fn alpha_at(image: &Image, uv: Vec2) -> Option<u8> {
+    if !matches!(
+        image.texture_descriptor.format,
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+    ) {
+        return None;
+    }
+    let (width, height) = (image.width(), image.height());
+    let x = ((uv.x * width as f32) as u32).min(width - 1);
+    let y = (((1. - uv.y) * height as f32) as u32).min(height - 1);
+    image.data.get(((y * width + x) * 4 + 3) as usize).copied()
+}
+
+/**
+* Outlines the tile under the cursor where the view draws it
+*/
+fn outline_hovered_tile(
+    mut gizmos: Gizmos,
+    hovered: Res<SelectedTilePos>,
+    layout: Option<Res<MapLayout>>,
+    view: Res<ViewRotation>,
+) {
+    let (Some(pos), Some(layout)) = (hovered.0, layout) else {
+        return;
+    };
+
+    let centre = Vec2::new(pos.x as f32, pos.y as f32);
+    let corners = [
+        (-0.5, -0.5),
+        (0.5, -0.5),
+        (0.5, 0.5),
+        (-0.5, 0.5),
+        (-0.5, -0.5),
+    ]
+    .map(|(dx, dy)| view.project(layout.point_to_world(centre + Vec2::new(dx, dy))));
+    gizmos.linestrip_2d(corners, HOVER_OUTLINE);
+}
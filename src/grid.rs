@@ -1,7 +1,10 @@
 use bevy::prelude::*;
+use bevy::sprite::Anchor;
 use bevy_common_assets::json::JsonAssetPlugin;
 use bevy_ecs_tilemap::prelude::*;
 
+use crate::building::trade::TradePartner;
+use crate::cli::Args;
 use crate::AppState;
 
 pub struct GridPlugin;
@@ -20,8 +23,15 @@ impl Plugin for GridPlugin {
 pub const TILE_W: u32 = 64;
 pub const TILE_H: u32 = 32;
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let level = asset_server.load("test.level.json");
+/// Tiles away from water at which derived fertility bottoms out
+const FERTILE_DISTANCE: f32 = 4.;
+const MIN_FERTILITY: f32 = 0.2;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, args: Option<Res<Args>>) {
+    let path = args
+        .and_then(|args| args.map.clone())
+        .unwrap_or_else(|| "test.level.json".to_string());
+    let level = asset_server.load(path);
     commands.insert_resource(CurrentLevel(level));
 }
 
@@ -43,7 +53,7 @@ fn create_terrain_tilemap(
         let tilemap_entity = commands.spawn_empty().id();
         let mut tile_storage = TileStorage::empty(map_size);
 
-        let tiles = level
+        let mut tiles = level
             .map
             .lines()
             .map(|line| {
@@ -58,8 +68,13 @@ fn create_terrain_tilemap(
             })
             .collect::<Vec<Vec<Terrain>>>();
 
-        for x in 0..tiles.len() {
-            for y in 0..tiles[0].len() {
+        match &level.fertility {
+            Some(fertility) => read_fertility(&mut tiles, fertility),
+            None => derive_fertility(&mut tiles),
+        }
+
+        for (x, row) in tiles.iter().enumerate() {
+            for (y, terrain) in row.iter().enumerate() {
                 let tile_pos = TilePos {
                     x: x as u32,
                     y: y as u32,
@@ -68,12 +83,12 @@ fn create_terrain_tilemap(
                 let tile_bundle = TileBundle {
                     position: tile_pos,
                     tilemap_id: TilemapId(tilemap_entity),
-                    texture_index: tiles[x][y].terrain_type.into(),
+                    texture_index: terrain.terrain_type.into(),
                     ..Default::default()
                 };
 
                 let tile_entity = commands
-                    .spawn((tiles[x][y].clone(), Occupied(None), tile_bundle))
+                    .spawn((terrain.clone(), Occupied(None), tile_bundle))
                     .id();
 
                 tile_storage.set(&tile_pos, tile_entity);
@@ -84,34 +99,187 @@ fn create_terrain_tilemap(
             x: TILE_W as f32,
             y: TILE_H as f32,
         };
-        let grid_size: TilemapGridSize = tile_size.into();
-        let map_type = TilemapType::Isometric(IsoCoordSystem::Diamond);
+        let layout = MapLayout::new(map_size);
 
         commands.entity(tilemap_entity).insert(TilemapBundle {
-            grid_size,
+            grid_size: layout.grid_size,
             size: map_size,
             storage: tile_storage,
             tile_size,
             texture: TilemapTexture::Single(texture_handle),
-            map_type,
-            transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
+            map_type: layout.map_type,
+            transform: layout.transform,
             ..Default::default()
         });
+        commands.insert_resource(layout);
 
         state.set(AppState::Level);
     }
 }
 
+/**
+* Reads fertility from a map laid out like `Level::map`, one digit per tile
+* from 0 (barren) to 9 (most fertile)
+*/
+fn read_fertility(tiles: &mut [Vec<Terrain>], fertility: &str) {
+    for (row, line) in tiles.iter_mut().zip(fertility.lines()) {
+        for (terrain, c) in row.iter_mut().zip(line.chars()) {
+            if let Some(digit) = c.to_digit(10) {
+                terrain.fertility = digit as f32 / 9.;
+            }
+        }
+    }
+}
+
+/**
+* Makes land fertile by how close it is to water, for levels that don't say
+*/
+fn derive_fertility(tiles: &mut [Vec<Terrain>]) {
+    let water: Vec<(usize, usize)> = tiles
+        .iter()
+        .enumerate()
+        .flat_map(|(x, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, terrain)| terrain.terrain_type == TerrainType::Water)
+                .map(move |(y, _)| (x, y))
+        })
+        .collect();
+
+    for (x, row) in tiles.iter_mut().enumerate() {
+        for (y, terrain) in row.iter_mut().enumerate() {
+            if terrain.terrain_type == TerrainType::Water {
+                continue;
+            }
+            let distance = water
+                .iter()
+                .map(|(wx, wy)| wx.abs_diff(x).max(wy.abs_diff(y)))
+                .min();
+            terrain.fertility = match distance {
+                Some(distance) => {
+                    (1. - (distance - 1) as f32 / FERTILE_DISTANCE).max(MIN_FERTILITY)
+                }
+                None => MIN_FERTILITY,
+            };
+        }
+    }
+}
+
 #[derive(serde::Deserialize, bevy::asset::Asset, bevy::reflect::TypePath, Debug)]
 pub struct Level {
     pub map: String,
     pub width: u32,
     pub height: u32,
+    /// Same layout as `map`, see `read_fertility`. Derived from water when missing
+    #[serde(default)]
+    pub fertility: Option<String>,
+    #[serde(default)]
+    pub trade_partners: Vec<TradePartner>,
 }
 
 #[derive(Resource)]
 pub struct CurrentLevel(pub Handle<Level>);
 
+/// Where the tiles of the loaded map lie in the world, as the simulation lays them out
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MapLayout {
+    pub size: TilemapSize,
+    pub grid_size: TilemapGridSize,
+    pub map_type: TilemapType,
+    /// Transform of the tilemap, centring it on the origin
+    pub transform: Transform,
+}
+
+impl MapLayout {
+    /// The layout of the terrain tilemap of a map `size` tiles across
+    pub fn new(size: TilemapSize) -> Self {
+        let grid_size = TilemapGridSize {
+            x: TILE_W as f32,
+            y: TILE_H as f32,
+        };
+        let map_type = TilemapType::Isometric(IsoCoordSystem::Diamond);
+        Self {
+            size,
+            grid_size,
+            map_type,
+            transform: get_tilemap_center_transform(&size, &grid_size, &map_type, 0.),
+        }
+    }
+
+    /**
+     * World position of the centre of the tile at `pos`
+     */
+    pub fn tile_to_world(&self, pos: TilePos) -> Vec2 {
+        let centre = pos.center_in_world(&self.grid_size, &self.map_type);
+        self.transform.transform_point(centre.extend(0.)).truncate()
+    }
+
+    /**
+     * World position of a point in tile coordinates, where tiles have their
+     * centre on whole numbers and their corners half a tile away
+     */
+    pub fn point_to_world(&self, point: Vec2) -> Vec2 {
+        let origin = self.tile_to_world(TilePos { x: 0, y: 0 });
+        let along_x = self.tile_to_world(TilePos { x: 1, y: 0 }) - origin;
+        let along_y = self.tile_to_world(TilePos { x: 0, y: 1 }) - origin;
+        origin + point.x * along_x + point.y * along_y
+    }
+
+    /**
+     * The tile under the world position `world`, if it's on the map
+     */
+    pub fn world_to_tile(&self, world: Vec2) -> Option<TilePos> {
+        let local = self
+            .transform
+            .compute_matrix()
+            .inverse()
+            .transform_point3(world.extend(0.))
+            .truncate();
+        TilePos::from_world_pos(&local, &self.size, &self.grid_size, &self.map_type)
+    }
+
+    /**
+     * World position of the middle of a footprint `size` tiles across, anchored at `anchor`
+     */
+    pub fn footprint_centre(&self, anchor: TilePos, size: (u32, u32)) -> Vec2 {
+        let (w, h) = size;
+        self.point_to_world(Vec2::new(
+            anchor.x as f32 + (w - 1) as f32 / 2.,
+            anchor.y as f32 + (h - 1) as f32 / 2.,
+        ))
+    }
+
+    /**
+     * Where a sprite of `sprite_size` drawn from `sprite_anchor` goes so that
+     * the middle of its bottom edge stands on the front corner of a footprint,
+     * below the middle of the footprint
+     */
+    pub fn footprint_to_world(
+        &self,
+        anchor: TilePos,
+        size: (u32, u32),
+        sprite_anchor: Anchor,
+        sprite_size: Vec2,
+    ) -> Vec2 {
+        let (w, h) = size;
+        let lowest = [
+            (0., 0.),
+            (w as f32, 0.),
+            (0., h as f32),
+            (w as f32, h as f32),
+        ]
+        .into_iter()
+        .map(|(dx, dy)| {
+            let corner = Vec2::new(anchor.x as f32 + dx, anchor.y as f32 + dy) - 0.5;
+            self.point_to_world(corner).y
+        })
+        .fold(f32::MAX, f32::min);
+        let base = Vec2::new(self.footprint_centre(anchor, size).x, lowest);
+        // The anchor is given from the middle of the sprite, in sprite sizes
+        base + (sprite_anchor.as_vec() + Vec2::new(0., 0.5)) * sprite_size
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum TerrainType {
     Grass = 0,
@@ -119,11 +287,18 @@ pub enum TerrainType {
     // Mountain = 2,
 }
 
+impl TerrainType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TerrainType::Grass => "Grass",
+            TerrainType::Water => "Water",
+        }
+    }
+}
+
 impl From<TerrainType> for TileTextureIndex {
     fn from(val: TerrainType) -> Self {
-        match val {
-            x => TileTextureIndex(x as u32),
-        }
+        TileTextureIndex(val as u32)
     }
 }
 
@@ -131,6 +306,8 @@ impl From<TerrainType> for TileTextureIndex {
 pub struct Terrain {
     pub terrain_type: TerrainType,
     pub is_buildable: bool,
+    /// How well crops grow here, from 0 to 1
+    pub fertility: f32,
     // pub is_coast: bool,
     // pub building_entity: Option<Entity>,
     // pub vegetation_entity: Option<Entity>,
@@ -141,6 +318,7 @@ impl Default for Terrain {
         Self {
             terrain_type: TerrainType::Grass,
             is_buildable: true,
+            fertility: MIN_FERTILITY,
             // is_coast: false,
             // building_entity: None,
             // vegetation_entity: None,
@@ -156,6 +334,7 @@ impl Terrain {
         Self {
             terrain_type: TerrainType::Grass,
             is_buildable: true,
+            fertility: MIN_FERTILITY,
             // is_coast: false,
             // building_entity: None,
             // vegetation_entity: None,
@@ -176,9 +355,108 @@ impl Terrain {
         Self {
             terrain_type: TerrainType::Water,
             is_buildable: false,
+            fertility: 0.,
             // is_coast: false,
             // building_entity: None,
             // vegetation_entity: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: [(u32, u32); 4] = [(12, 12), (32, 32), (7, 20), (1, 1)];
+
+    #[test]
+    fn tiles_round_trip() {
+        for (x, y) in SIZES {
+            let layout = MapLayout::new(TilemapSize { x, y });
+            for pos in [
+                TilePos { x: 0, y: 0 },
+                TilePos { x: x - 1, y: 0 },
+                TilePos { x: 0, y: y - 1 },
+                TilePos { x: x - 1, y: y - 1 },
+                TilePos { x: x / 2, y: y / 3 },
+            ] {
+                assert_eq!(layout.world_to_tile(layout.tile_to_world(pos)), Some(pos));
+            }
+        }
+    }
+
+    #[test]
+    fn map_is_centred_on_the_origin() {
+        for (x, y) in SIZES {
+            let layout = MapLayout::new(TilemapSize { x, y });
+            let corners = [
+                layout.tile_to_world(TilePos { x: 0, y: 0 }),
+                layout.tile_to_world(TilePos { x: x - 1, y: 0 }),
+                layout.tile_to_world(TilePos { x: 0, y: y - 1 }),
+                layout.tile_to_world(TilePos { x: x - 1, y: y - 1 }),
+            ];
+            let low = corners.into_iter().fold(Vec2::MAX, Vec2::min);
+            let high = corners.into_iter().fold(Vec2::MIN, Vec2::max);
+            let middle = (low + high) / 2.;
+            assert!(
+                middle.abs().max_element() <= TILE_W as f32 / 2.,
+                "{x}x{y}: {middle}"
+            );
+        }
+    }
+
+    #[test]
+    fn neighbours_are_half_a_tile_apart() {
+        let layout = MapLayout::new(TilemapSize { x: 12, y: 12 });
+        let pos = TilePos { x: 4, y: 7 };
+        let step_x = layout.tile_to_world(TilePos { x: 5, y: 7 }) - layout.tile_to_world(pos);
+        let step_y = layout.tile_to_world(TilePos { x: 4, y: 8 }) - layout.tile_to_world(pos);
+        let half = Vec2::new(TILE_W as f32, TILE_H as f32) / 2.;
+        assert_eq!(step_x.abs(), half);
+        assert_eq!(step_y.abs(), half);
+    }
+
+    #[test]
+    fn single_tile_footprint_stands_on_its_front_corner() {
+        for (x, y) in SIZES {
+            let layout = MapLayout::new(TilemapSize { x, y });
+            let pos = TilePos { x: x / 2, y: y / 2 };
+            let centre = layout.tile_to_world(pos);
+            assert_eq!(layout.footprint_centre(pos, (1, 1)), centre);
+
+            let base = layout.footprint_to_world(pos, (1, 1), Anchor::BottomCenter, Vec2::ZERO);
+            assert_eq!(base, centre - Vec2::new(0., TILE_H as f32 / 2.));
+        }
+    }
+
+    #[test]
+    fn footprints_stand_under_their_middle() {
+        for (x, y) in SIZES.into_iter().filter(|(x, y)| *x >= 3 && *y >= 3) {
+            let layout = MapLayout::new(TilemapSize { x, y });
+            let anchor = TilePos { x: 0, y: 0 };
+            for size in [(2, 2), (3, 3), (1, 2), (2, 1)] {
+                let centre = layout.footprint_centre(anchor, size);
+                let base =
+                    layout.footprint_to_world(anchor, size, Anchor::BottomCenter, Vec2::ZERO);
+                assert_eq!(base.x, centre.x, "{size:?} on {x}x{y}");
+                // The front corner lies half a tile below the middle for every tile of depth
+                let depth = (size.0 + size.1) as f32 / 2.;
+                assert_eq!(
+                    centre.y - base.y,
+                    depth * TILE_H as f32 / 2.,
+                    "{size:?} on {x}x{y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sprite_anchor_moves_the_sprite_onto_the_base() {
+        let layout = MapLayout::new(TilemapSize { x: 12, y: 12 });
+        let anchor = TilePos { x: 3, y: 5 };
+        let sprite_size = Vec2::new(120., 100.);
+        let base = layout.footprint_to_world(anchor, (2, 2), Anchor::BottomCenter, sprite_size);
+        let centred = layout.footprint_to_world(anchor, (2, 2), Anchor::Center, sprite_size);
+        assert_eq!(centred - base, Vec2::new(0., 50.));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use bevy::asset::LoadState;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use serde::{Deserialize, Serialize};
+
+use crate::building::components::{Building, BuildingType, Orientation};
+use crate::building::housing::Household;
+use crate::building::prices::MarketPrices;
+use crate::building::risk::{OnFire, Rubble};
+use crate::building::site::UnderConstruction;
+use crate::building::storage::Storage;
+use crate::building::water::WaterCoverage;
+use crate::camera::CameraBookmarks;
+use crate::cli::Args;
+use crate::command::{
+    state_hash, CommandSet, PlayerCommand, Replay, ReplayPlayback, ReplayRecorder, SimulationTick,
+};
+use crate::grid::CurrentLevel;
+use crate::resources::{GlobalResources, Ledger};
+use crate::time::Calendar;
+use crate::SimulationPlugins;
+
+/// Simulated time per update, matching the default `FixedUpdate` rate so every
+/// update runs the simulation schedule exactly once.
+const STEP: Duration = Duration::from_nanos(1_000_000_000 / 64);
+
+pub struct HeadlessPlugin;
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuildScript>();
+        app.add_systems(FixedUpdate, apply_build_script.in_set(CommandSet::Issue));
+    }
+}
+
+/// A building to place once the calendar reaches `month`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedBuild {
+    pub month: u32,
+    pub building: BuildingType,
+    pub x: u32,
+    pub y: u32,
+    #[serde(default)]
+    pub orientation: Orientation,
+}
+
+/// Scripted builds that have not been applied yet.
+#[derive(Resource, Default)]
+pub struct BuildScript(pub Vec<ScriptedBuild>);
+
+impl BuildScript {
+    pub fn from_file(path: &str) -> Self {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Cannot read build script {path}: {e}"));
+        let builds = serde_json::from_str(&contents)
+            .unwrap_or_else(|e| panic!("Invalid build script {path}: {e}"));
+        Self(builds)
+    }
+}
+
+fn apply_build_script(
+    calendar: Res<Calendar>,
+    mut script: ResMut<BuildScript>,
+    mut player_commands: EventWriter<PlayerCommand>,
+) {
+    script.0.retain(|build| {
+        if build.month > calendar.month {
+            return true;
+        }
+        player_commands.send(PlayerCommand::PlaceBuilding {
+            building_type: build.building,
+            x: build.x,
+            y: build.y,
+            orientation: build.orientation,
+        });
+        false
+    });
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationSummary {
+    pub months: u32,
+    pub gold: i32,
+    /// Workers employed by all finished buildings
+    pub population: u32,
+    pub buildings: BTreeMap<String, u32>,
+    pub under_construction: u32,
+    pub on_fire: u32,
+    /// Tiles still covered in rubble
+    pub rubble: u32,
+    /// Houses within range of a connected fountain
+    pub watered_houses: u32,
+    /// Goods kept in all buildings together
+    pub stored: BTreeMap<String, u32>,
+    /// People living in houses
+    pub residents: u32,
+    /// Houses whose residents went hungry last month
+    pub hungry_houses: u32,
+    /// Gold booked over the months the ledger keeps, by entry
+    pub ledger: BTreeMap<String, i32>,
+    /// Prices that moved away from the usual ones, as a share of them
+    pub prices: BTreeMap<String, f32>,
+}
+
+impl SimulationSummary {
+    pub fn collect(world: &mut World) -> Self {
+        let mut population = 0;
+        let mut buildings = BTreeMap::new();
+        let mut under_construction = 0;
+        let mut on_fire = 0;
+        let mut building_q = world.query_filtered::<(
+            &BuildingType,
+            Option<&UnderConstruction>,
+            Option<&OnFire>,
+            &Storage,
+        ), With<Building>>();
+        let mut stored = BTreeMap::new();
+        for (building_type, site, fire, storage) in building_q.iter(world) {
+            for (good, amount) in storage.goods.iter() {
+                *stored.entry(good.name()).or_insert(0) += amount;
+            }
+            if fire.is_some() {
+                on_fire += 1;
+            }
+            if site.is_some() {
+                under_construction += 1;
+                continue;
+            }
+            population += building_type.occupation();
+            *buildings.entry(building_type.name()).or_insert(0) += 1;
+        }
+
+        Self {
+            months: world.resource::<Calendar>().month,
+            gold: world.resource::<GlobalResources>().gold,
+            population,
+            buildings,
+            under_construction,
+            on_fire,
+            rubble: world
+                .query_filtered::<(), With<Rubble>>()
+                .iter(world)
+                .count() as u32,
+            watered_houses: world
+                .query_filtered::<(), With<WaterCoverage>>()
+                .iter(world)
+                .count() as u32,
+            stored,
+            residents: world
+                .query::<&Household>()
+                .iter(world)
+                .map(|household| household.residents)
+                .sum(),
+            hungry_houses: world
+                .query::<&Household>()
+                .iter(world)
+                .filter(|household| household.hungry_months > 0)
+                .count() as u32,
+            ledger: world
+                .resource::<Ledger>()
+                .totals()
+                .into_iter()
+                .map(|(entry, amount)| (entry.name(), amount))
+                .collect(),
+            prices: world
+                .resource::<MarketPrices>()
+                .factors
+                .iter()
+                .map(|(good, factor)| (good.name(), *factor))
+                .collect(),
+        }
+    }
+}
+
+/// Runs the simulation without a window and prints a JSON summary of the final
+/// state to stdout. Runs for `args.months` in-game months, or when `args.replay`
+/// is set, plays the replay back and exits with an error if its final state differs.
+pub fn run(mut args: Args) {
+    let months = args.months;
+    let replay = args.replay.as_deref().map(Replay::from_file);
+    // Scripted builds were recorded along with every other command.
+    let script = match (&replay, args.script.as_deref()) {
+        (None, Some(path)) => BuildScript::from_file(path),
+        _ => BuildScript::default(),
+    };
+    let (playback, bookmarks) = match &replay {
+        Some(replay) => {
+            args.map = replay.level.clone();
+            (
+                replay.playback(),
+                CameraBookmarks(replay.camera_bookmarks.clone()),
+            )
+        }
+        None => (ReplayPlayback::default(), CameraBookmarks::default()),
+    };
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((
+            LogPlugin::default(),
+            StatesPlugin,
+            AssetPlugin::default(),
+            ImagePlugin::default(),
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(STEP))
+        .insert_resource(args)
+        .add_plugins(SimulationPlugins)
+        .add_plugins(HeadlessPlugin)
+        .insert_resource(script)
+        .insert_resource(playback)
+        .insert_resource(bookmarks);
+
+    app.finish();
+    app.cleanup();
+
+    let finished = |world: &World| match &replay {
+        Some(replay) => world.resource::<SimulationTick>().0 >= replay.final_tick,
+        None => world.resource::<Calendar>().month >= months,
+    };
+
+    while !finished(app.world()) {
+        app.update();
+
+        let level = &app.world().resource::<CurrentLevel>().0;
+        if let Some(LoadState::Failed(e)) =
+            app.world().resource::<AssetServer>().get_load_state(level)
+        {
+            error!("Cannot load level: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    ReplayRecorder::save(app.world_mut());
+
+    let summary = SimulationSummary::collect(app.world_mut());
+    println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+
+    if let Some(replay) = replay {
+        let hash = state_hash(app.world_mut());
+        if hash == replay.state_hash {
+            info!("Replay verified, state hash {hash}");
+        } else {
+            error!(
+                "Replay diverged: state hash {hash}, expected {}",
+                replay.state_hash
+            );
+            std::process::exit(1);
+        }
+    }
+}
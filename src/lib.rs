@@ -0,0 +1,46 @@
+pub mod building;
+pub mod camera;
+pub mod cli;
+pub mod command;
+pub mod cursor;
+pub mod grid;
+pub mod headless;
+pub mod minimap;
+pub mod resources;
+pub mod selection;
+pub mod time;
+pub mod ui;
+pub mod view;
+
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+
+use building::BuildingPlugin;
+use command::CommandPlugin;
+use grid::GridPlugin;
+use resources::ResourcesPlugin;
+use time::TimeControlsPlugin;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+pub enum AppState {
+    #[default]
+    Loading,
+    Level,
+}
+
+/// The game simulation without any rendering, windowing, input or UI, which
+/// `BuildingInputPlugin` and the other client plugins add on top.
+///
+/// Needs states, assets and images to be available, which `DefaultPlugins`
+/// provides in the windowed game and `headless::run` sets up on top of `MinimalPlugins`.
+pub struct SimulationPlugins;
+impl PluginGroup for SimulationPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(CommandPlugin)
+            .add(TimeControlsPlugin)
+            .add(GridPlugin)
+            .add(BuildingPlugin)
+            .add(ResourcesPlugin)
+    }
+}
@@ -1,55 +1,59 @@
-mod building;
-mod camera;
-mod cli;
-mod cursor;
-mod grid;
-mod resources;
-mod time;
-mod ui;
-
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 use clap::Parser;
 
-use building::BuildingPlugin;
-use camera::CameraPlugin;
-use cli::Args;
-use cursor::CursorPlugin;
-use grid::GridPlugin;
-use resources::ResourcesPlugin;
-use time::TimeControlsPlugin;
-use ui::UiPlugin;
-
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
-pub enum AppState {
-    #[default]
-    Loading,
-    Level,
-}
+use bevy3::building::BuildingInputPlugin;
+use bevy3::camera::{CameraBookmarks, CameraPlugin};
+use bevy3::cli::Args;
+use bevy3::command::Replay;
+use bevy3::cursor::CursorPlugin;
+use bevy3::headless;
+use bevy3::minimap::MinimapPlugin;
+use bevy3::selection::SelectionPlugin;
+use bevy3::ui::UiPlugin;
+use bevy3::view::ViewPlugin;
+use bevy3::SimulationPlugins;
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    if args.headless || args.replay.is_some() {
+        return headless::run(args);
+    }
+
+    let replay = args.load.as_deref().map(Replay::from_file);
+    if let Some(replay) = &replay {
+        args.map = replay.level.clone();
+    }
+
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: String::from("Iso Diamond Example"),
+                    ..Default::default()
+                }),
+                ..default()
+            })
+            .set(ImagePlugin::default_nearest()),
+    )
+    .insert_resource(args)
+    .add_plugins(SimulationPlugins)
+    .add_plugins(TilemapPlugin) // This is the plugin for the tilemap
+    .add_plugins(CameraPlugin)
+    .add_plugins(ViewPlugin)
+    .add_plugins(CursorPlugin)
+    .add_plugins(SelectionPlugin)
+    .add_plugins(BuildingInputPlugin)
+    .add_plugins(UiPlugin)
+    .add_plugins(MinimapPlugin);
+
+    // In place before the level loads and the camera centres on it
+    if let Some(replay) = replay {
+        app.insert_resource(replay.playback())
+            .insert_resource(CameraBookmarks(replay.camera_bookmarks));
+    }
 
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: String::from("Iso Diamond Example"),
-                        ..Default::default()
-                    }),
-                    ..default()
-                })
-                .set(ImagePlugin::default_nearest()),
-        )
-        .insert_resource(args)
-        .add_plugins(TimeControlsPlugin)
-        .add_plugins(TilemapPlugin) // This is the plugin for the tilemap
-        .add_plugins(GridPlugin)
-        .add_plugins(CameraPlugin)
-        .add_plugins(CursorPlugin)
-        .add_plugins(BuildingPlugin)
-        .add_plugins(ResourcesPlugin)
-        .add_plugins(UiPlugin)
-        .run();
+    app.run();
 }
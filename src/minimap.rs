@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32},
+    EguiContexts,
+};
+
+use crate::building::components::{
+    AnchorTile, Building, BuildingCategory, BuildingType, Orientation,
+};
+use crate::camera::CameraFollow;
+use crate::grid::{Terrain, TerrainType};
+use crate::view::ViewRotation;
+use crate::AppState;
+
+/// Width and height of the minimap, in points
+const MINIMAP_SIZE: f32 = 200.;
+/// Radius of the dot marking a building, in points
+const DOT_RADIUS: f32 = 2.;
+
+pub struct MinimapPlugin;
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Minimap>();
+        app.add_systems(
+            Update,
+            (paint_terrain, track_buildings, ui_minimap)
+                .chain()
+                .run_if(in_state(AppState::Level)),
+        );
+    }
+}
+
+/// What the minimap shows, kept up to date as tiles and buildings change
+/// rather than gathered again every frame
+#[derive(Resource, Default)]
+pub struct Minimap {
+    /// Tiles across and down the map the terrain was painted for
+    size: UVec2,
+    /// Colour of each tile, a row of pixels for each y
+    terrain: Vec<Color32>,
+    /// Whether the texture lags behind `terrain`
+    stale: bool,
+    texture: Option<egui::TextureHandle>,
+    /// Middle of each building in tile coordinates, and what kind it is
+    buildings: HashMap<Entity, (Vec2, BuildingCategory)>,
+}
+
+fn terrain_color(terrain: &Terrain) -> Color32 {
+    match terrain.terrain_type {
+        TerrainType::Grass => {
+            // Greener the more fertile
+            let dry = [150., 160., 90.];
+            let lush = [60., 140., 40.];
+            let [r, g, b] = [0, 1, 2]
+                .map(|i| (dry[i] + (lush[i] - dry[i]) * terrain.fertility.clamp(0., 1.)) as u8);
+            Color32::from_rgb(r, g, b)
+        }
+        TerrainType::Water => Color32::from_rgb(50, 90, 170),
+    }
+}
+
+fn category_color(category: BuildingCategory) -> Color32 {
+    match category {
+        BuildingCategory::Housing => Color32::from_rgb(230, 200, 120),
+        BuildingCategory::Entertainment => Color32::from_rgb(200, 90, 200),
+        BuildingCategory::Services => Color32::from_rgb(220, 60, 50),
+        BuildingCategory::Water => Color32::from_rgb(120, 220, 255),
+        BuildingCategory::Food => Color32::from_rgb(250, 160, 40),
+        BuildingCategory::Trade => Color32::WHITE,
+        BuildingCategory::Industry => Color32::from_rgb(140, 120, 100),
+    }
+}
+
+/**
+* Repaints the pixels of tiles whose terrain changed, starting over when
+* a map of another size gets loaded
+*/
+fn paint_terrain(
+    mut minimap: ResMut<Minimap>,
+    tilemap_q: Query<&TilemapSize>,
+    tile_q: Query<(&TilePos, &Terrain), Changed<Terrain>>,
+) {
+    let Ok(map_size) = tilemap_q.get_single() else {
+        return;
+    };
+    let size = UVec2::new(map_size.x, map_size.y);
+    if minimap.size != size {
+        minimap.size = size;
+        minimap.terrain = vec![Color32::BLACK; (size.x * size.y) as usize];
+        minimap.stale = true;
+    }
+
+    for (pos, terrain) in &tile_q {
+        let pixel = (pos.y * size.x + pos.x) as usize;
+        minimap.terrain[pixel] = terrain_color(terrain);
+        minimap.stale = true;
+    }
+}
+
+/**
+* Keeps the dots of buildings in step with those placed, changed or removed
+*/
+#[allow(clippy::type_complexity)]
+fn track_buildings(
+    mut minimap: ResMut<Minimap>,
+    building_q: Query<
+        (Entity, &BuildingType, &AnchorTile, &Orientation),
+        (
+            With<Building>,
+            Or<(Added<Building>, Changed<BuildingType>, Changed<Orientation>)>,
+        ),
+    >,
+    mut removed: RemovedComponents<Building>,
+) {
+    for building in removed.read() {
+        minimap.buildings.remove(&building);
+    }
+
+    for (building, building_type, anchor, orientation) in &building_q {
+        let (w, h) = building_type.oriented_size(*orientation);
+        let middle = Vec2::new(
+            anchor.0.x as f32 + (w - 1) as f32 / 2.,
+            anchor.0.y as f32 + (h - 1) as f32 / 2.,
+        );
+        minimap
+            .buildings
+            .insert(building, (middle, building_type.category()));
+    }
+}
+
+/**
+* Draws the map turned like the view, with a dot for each building and the
+* part the camera shows framed, moving the camera wherever it's clicked and
+* letting go of whatever it follows
+*/
+fn ui_minimap(
+    mut contexts: EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    view: Res<ViewRotation>,
+    mut follow: ResMut<CameraFollow>,
+    tilemap_q: Query<(&TilemapGridSize, &TilemapType, &Transform), Without<Camera>>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let Ok((grid_size, map_type, map_transform)) = tilemap_q.get_single() else {
+        return;
+    };
+    if minimap.size == UVec2::ZERO {
+        return;
+    }
+    let ctx = contexts.ctx_mut().clone();
+
+    if minimap.stale {
+        let image = egui::ColorImage {
+            size: [minimap.size.x as usize, minimap.size.y as usize],
+            pixels: minimap.terrain.clone(),
+        };
+        match &mut minimap.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => {
+                minimap.texture =
+                    Some(ctx.load_texture("minimap", image, egui::TextureOptions::NEAREST));
+            }
+        }
+        minimap.stale = false;
+    }
+    let Some(texture) = minimap.texture.as_ref().map(|texture| texture.id()) else {
+        return;
+    };
+
+    // Where a point in tile coordinates, fractions included, is drawn in the world
+    let origin = TilePos { x: 0, y: 0 }.center_in_world(grid_size, map_type);
+    let along_x = TilePos { x: 1, y: 0 }.center_in_world(grid_size, map_type) - origin;
+    let along_y = TilePos { x: 0, y: 1 }.center_in_world(grid_size, map_type) - origin;
+    let drawn = |point: Vec2| {
+        let logical = origin + point.x * along_x + point.y * along_y;
+        view.project(map_transform.transform_point(logical.extend(0.)).truncate())
+    };
+
+    let size = minimap.size.as_vec2();
+    let corners = [
+        Vec2::new(0., 0.),
+        Vec2::new(size.x, 0.),
+        size,
+        Vec2::new(0., size.y),
+    ];
+    let drawn_corners = corners.map(|corner| drawn(corner - 0.5));
+    let low = drawn_corners.into_iter().fold(Vec2::MAX, Vec2::min);
+    let high = drawn_corners.into_iter().fold(Vec2::MIN, Vec2::max);
+    let middle = (low + high) / 2.;
+    let scale = (MINIMAP_SIZE / (high - low)).min_element();
+
+    egui::Window::new("Minimap")
+        .resizable(false)
+        .show(&ctx, |ui| {
+            let (response, painter) = ui.allocate_painter(
+                egui::vec2(MINIMAP_SIZE, MINIMAP_SIZE),
+                egui::Sense::click_and_drag(),
+            );
+            let rect = response.rect;
+            let to_minimap = |world: Vec2| {
+                let offset = (world - middle) * scale;
+                rect.center() + egui::vec2(offset.x, -offset.y)
+            };
+
+            let mut mesh = egui::Mesh::with_texture(texture);
+            for (corner, world) in corners.iter().zip(drawn_corners) {
+                let uv = *corner / size;
+                mesh.vertices.push(egui::epaint::Vertex {
+                    pos: to_minimap(world),
+                    uv: egui::pos2(uv.x, uv.y),
+                    color: Color32::WHITE,
+                });
+            }
+            mesh.add_triangle(0, 1, 2);
+            mesh.add_triangle(0, 2, 3);
+            painter.add(mesh);
+
+            for (point, category) in minimap.buildings.values() {
+                painter.circle_filled(
+                    to_minimap(drawn(*point)),
+                    DOT_RADIUS,
+                    category_color(*category),
+                );
+            }
+
+            for (mut transform, ortho) in &mut camera_q {
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    follow.0 = None;
+                    let offset = (pointer - rect.center()) / scale;
+                    let target = middle + Vec2::new(offset.x, -offset.y);
+                    transform.translation.x = target.x;
+                    transform.translation.y = target.y;
+                }
+
+                let centre = transform.translation.truncate();
+                let viewport = egui::Rect::from_two_pos(
+                    to_minimap(centre + ortho.area.min),
+                    to_minimap(centre + ortho.area.max),
+                );
+                painter.rect_stroke(viewport, 0., (1., Color32::WHITE));
+            }
+        });
+}
@@ -1,13 +1,126 @@
+use std::collections::BTreeMap;
+
 use bevy::prelude::*;
 
+use crate::building::components::BuildingType;
+use crate::building::site::Finished;
+use crate::building::upkeep::Condition;
+use crate::command::{CommandSet, PlayerCommand};
+use crate::time::NewMonth;
+
+pub const MAX_TAX_RATE: u32 = 25;
+/// Months of bookings the ledger keeps
+const LEDGER_MONTHS: u32 = 12;
+
+/// Goods that can be stockpiled
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub enum Good {
+    Timber,
+    Stone,
+    Wheat,
+    Fruit,
+    Fish,
+    Meat,
+}
+
+impl Good {
+    /// Goods people eat, each one adding to the variety of their diet
+    pub const FOOD: [Good; 4] = [Good::Wheat, Good::Fruit, Good::Fish, Good::Meat];
+    pub const ALL: [Good; 6] = [
+        Good::Timber,
+        Good::Stone,
+        Good::Wheat,
+        Good::Fruit,
+        Good::Fish,
+        Good::Meat,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            Good::Timber => "Timber".to_string(),
+            Good::Stone => "Stone".to_string(),
+            Good::Wheat => "Wheat".to_string(),
+            Good::Fruit => "Fruit".to_string(),
+            Good::Fish => "Fish".to_string(),
+            Good::Meat => "Meat".to_string(),
+        }
+    }
+
+    /// Food is kept in granaries, everything else in the city stockpile
+    pub fn is_food(&self) -> bool {
+        Good::FOOD.contains(self)
+    }
+}
+
 #[derive(Resource)]
 pub struct GlobalResources {
     pub gold: i32,
+    /// Percentage of the wages paid each month that comes back as taxes
+    pub tax_rate: u32,
+    pub goods: BTreeMap<Good, u32>,
 }
 
 impl Default for GlobalResources {
     fn default() -> Self {
-        Self { gold: 1000 }
+        Self {
+            gold: 1000,
+            tax_rate: 7,
+            goods: BTreeMap::from([(Good::Timber, 40), (Good::Stone, 40)]),
+        }
+    }
+}
+
+/// What gold was earned or spent on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerEntry {
+    Taxes,
+    Exports,
+    Wages,
+    Upkeep,
+    Imports,
+}
+
+impl LedgerEntry {
+    pub fn name(&self) -> String {
+        match self {
+            LedgerEntry::Taxes => "Taxes".to_string(),
+            LedgerEntry::Exports => "Exports".to_string(),
+            LedgerEntry::Wages => "Wages".to_string(),
+            LedgerEntry::Upkeep => "Upkeep".to_string(),
+            LedgerEntry::Imports => "Imports".to_string(),
+        }
+    }
+}
+
+/// Gold booked in each of the last months, spending counted as negative
+#[derive(Resource, Default, Debug)]
+pub struct Ledger {
+    pub months: BTreeMap<u32, BTreeMap<LedgerEntry, i32>>,
+}
+
+impl Ledger {
+    pub fn book(&mut self, month: u32, entry: LedgerEntry, amount: i32) {
+        *self
+            .months
+            .entry(month)
+            .or_default()
+            .entry(entry)
+            .or_insert(0) += amount;
+        self.months
+            .retain(|booked, _| *booked + LEDGER_MONTHS > month);
+    }
+
+    /**
+     * Everything booked over the months the ledger keeps, by entry
+     */
+    pub fn totals(&self) -> BTreeMap<LedgerEntry, i32> {
+        let mut totals = BTreeMap::new();
+        for (entry, amount) in self.months.values().flatten() {
+            *totals.entry(*entry).or_insert(0) += amount;
+        }
+        totals
     }
 }
 
@@ -15,5 +128,35 @@ pub struct ResourcesPlugin;
 impl Plugin for ResourcesPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<GlobalResources>();
+        app.init_resource::<Ledger>();
+        app.add_systems(FixedUpdate, set_tax_rate.in_set(CommandSet::Apply));
+    }
+}
+
+fn set_tax_rate(mut commands: EventReader<PlayerCommand>, mut resources: ResMut<GlobalResources>) {
+    for command in commands.read() {
+        if let PlayerCommand::SetTaxRate(rate) = command {
+            resources.tax_rate = (*rate).min(MAX_TAX_RATE);
+        }
+    }
+}
+
+pub fn collect_taxes(
+    mut new_month: EventReader<NewMonth>,
+    mut resources: ResMut<GlobalResources>,
+    mut ledger: ResMut<Ledger>,
+    q: Query<(&BuildingType, &Condition), Finished>,
+) {
+    for event in new_month.read() {
+        // Buildings in poor condition take less work, and so pay less in taxes.
+        let wages: u32 = q
+            .iter()
+            .map(|(building, condition)| {
+                (building.occupation() as f32 * 30. * condition.efficiency()) as u32
+            })
+            .sum();
+        let taxes = (wages * resources.tax_rate / 100) as i32;
+        resources.gold += taxes;
+        ledger.book(event.month, LedgerEntry::Taxes, taxes);
     }
 }
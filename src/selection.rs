@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::building::components::{AnchorTile, BuildingType, Orientation};
+use crate::building::BuildingMode;
+use crate::cursor::{HoveredBuilding, SelectedTilePos};
+use crate::AppState;
+
+/// Tint of the selected tiles
+const SELECTED_COLOR: Color = Color::srgb(1., 1., 0.55);
+
+pub struct SelectionPlugin;
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedArea>();
+        app.add_systems(
+            Update,
+            (
+                select_tiles.run_if(in_state(BuildingMode::Off)),
+                tint_selection,
+            )
+                .chain()
+                .run_if(in_state(AppState::Level)),
+        );
+    }
+}
+
+/// Tiles picked for a closer look, every tile between two opposite corners
+#[derive(Resource, Default, Debug)]
+pub struct SelectedArea {
+    pub corners: Option<(TilePos, TilePos)>,
+    /// The building clicked on, whose footprint the corners frame
+    pub building: Option<Entity>,
+    /// Where the box being dragged out started
+    drag_start: Option<TilePos>,
+}
+
+impl SelectedArea {
+    pub fn contains(&self, pos: TilePos) -> bool {
+        self.corners.is_some_and(|(a, b)| {
+            (a.x.min(b.x)..=a.x.max(b.x)).contains(&pos.x)
+                && (a.y.min(b.y)..=a.y.max(b.y)).contains(&pos.y)
+        })
+    }
+
+    pub fn tiles(&self) -> Vec<TilePos> {
+        let Some((a, b)) = self.corners else {
+            return vec![];
+        };
+        (a.x.min(b.x)..=a.x.max(b.x))
+            .flat_map(|x| (a.y.min(b.y)..=a.y.max(b.y)).map(move |y| TilePos { x, y }))
+            .collect()
+    }
+}
+
+/**
+* Selects the building drawn where it's clicked, or else the tile clicked on.
+* With Shift held it selects every tile in the box dragged out from there.
+* Clicking off the map or Escape clears the selection
+*/
+fn select_tiles(
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    hovered: Res<SelectedTilePos>,
+    hovered_building: Res<HoveredBuilding>,
+    building_q: Query<(&BuildingType, &AnchorTile, &Orientation)>,
+    mut contexts: EguiContexts,
+    mut area: ResMut<SelectedArea>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        area.corners = None;
+        area.building = None;
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Left) && !contexts.ctx_mut().is_pointer_over_area() {
+        let dragging = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let building = hovered_building
+            .0
+            .filter(|_| !dragging)
+            .and_then(|entity| Some((entity, building_q.get(entity).ok()?)));
+        match building {
+            Some((entity, (building_type, anchor, orientation))) => {
+                let (w, h) = building_type.oriented_size(*orientation);
+                let far = TilePos {
+                    x: anchor.0.x + w - 1,
+                    y: anchor.0.y + h - 1,
+                };
+                area.corners = Some((anchor.0, far));
+                area.building = Some(entity);
+            }
+            None => {
+                area.corners = hovered.0.map(|pos| (pos, pos));
+                area.building = None;
+            }
+        }
+        area.drag_start = hovered.0.filter(|_| dragging);
+    }
+
+    let Some(start) = area.drag_start else {
+        return;
+    };
+    if !mouse_buttons.pressed(MouseButton::Left) {
+        area.drag_start = None;
+    } else if let Some(end) = hovered.0 {
+        area.corners = Some((start, end));
+    }
+}
+
+/**
+* Tints the selected tiles whenever the selection changes
+*/
+fn tint_selection(area: Res<SelectedArea>, mut tile_q: Query<(&TilePos, &mut TileColor)>) {
+    if !area.is_changed() {
+        return;
+    }
+
+    for (pos, mut color) in &mut tile_q {
+        let tint = if area.contains(*pos) {
+            SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+        if color.0 != tint {
+            color.0 = tint;
+        }
+    }
+}
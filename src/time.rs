@@ -1,17 +1,113 @@
 use bevy::prelude::*;
 
+use crate::command::{CommandSet, PlayerCommand};
+use crate::AppState;
+
 pub struct TimeControlsPlugin;
 impl Plugin for TimeControlsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_state(TimeState::Running);
         app.insert_state(TimeSpeed::Normal);
-        app.insert_resource(GameTimer(Timer::from_seconds(7.0, TimerMode::Repeating)));
+        app.insert_resource(GameTimer::new(TimeSpeed::Normal));
+        app.init_resource::<Calendar>();
+        app.add_event::<NewMonth>();
+        app.add_systems(FixedUpdate, apply_time_commands.in_set(CommandSet::Apply));
+        app.add_systems(
+            FixedUpdate,
+            advance_calendar
+                .after(CommandSet::Apply)
+                .run_if(time_running)
+                .run_if(in_state(AppState::Level)),
+        );
     }
 }
 
 #[derive(Resource)]
 pub struct GameTimer(pub Timer);
 
+impl GameTimer {
+    pub fn new(speed: TimeSpeed) -> Self {
+        Self(Timer::from_seconds(
+            speed.month_duration(),
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+/// In-game months elapsed since the level was loaded.
+#[derive(Resource, Default, Debug)]
+pub struct Calendar {
+    pub month: u32,
+}
+
+impl Calendar {
+    pub fn season(&self) -> Season {
+        Season::of(self.month)
+    }
+}
+
+/// Quarter of the year, three months each starting with spring
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// Crops are brought in as this season starts
+    pub const HARVEST: Season = Season::Autumn;
+
+    pub fn of(month: u32) -> Self {
+        match month % 12 {
+            0..=2 => Season::Spring,
+            3..=5 => Season::Summer,
+            6..=8 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    /// Whether `month` is the first one of its season
+    pub fn starts_at(month: u32) -> bool {
+        month.is_multiple_of(3)
+    }
+
+    /// Whether crops grow during the season
+    pub fn is_growing(&self) -> bool {
+        matches!(self, Season::Spring | Season::Summer)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Season::Spring => "Spring",
+            Season::Summer => "Summer",
+            Season::Autumn => "Autumn",
+            Season::Winter => "Winter",
+        }
+    }
+}
+
+/// Sent every time the `GameTimer` completes an in-game month.
+#[derive(Event, Debug)]
+pub struct NewMonth {
+    pub month: u32,
+}
+
+pub fn advance_calendar(
+    time: Res<Time>,
+    mut timer: ResMut<GameTimer>,
+    mut calendar: ResMut<Calendar>,
+    mut new_month: EventWriter<NewMonth>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        calendar.month += 1;
+        new_month.send(NewMonth {
+            month: calendar.month,
+        });
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, States)]
 pub enum TimeState {
     Paused,
@@ -19,7 +115,9 @@ pub enum TimeState {
     Running,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, States)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, Default, States, serde::Serialize, serde::Deserialize,
+)]
 pub enum TimeSpeed {
     #[default]
     Normal,
@@ -27,3 +125,45 @@ pub enum TimeSpeed {
     Faster,
     Fastest,
 }
+
+impl TimeSpeed {
+    /// Real seconds in an in-game month
+    pub fn month_duration(&self) -> f32 {
+        match self {
+            TimeSpeed::Normal => 7.0,
+            TimeSpeed::Fast => 3.5,
+            TimeSpeed::Faster => 1.75,
+            TimeSpeed::Fastest => 0.8525,
+        }
+    }
+}
+
+/**
+* Like `in_state(TimeState::Running)`, but already honours a pause or resume
+* requested earlier in this same tick, so commands take effect deterministically.
+*/
+pub fn time_running(state: Res<State<TimeState>>, next_state: Res<NextState<TimeState>>) -> bool {
+    match next_state.as_ref() {
+        NextState::Pending(next) => *next == TimeState::Running,
+        NextState::Unchanged => *state.get() == TimeState::Running,
+    }
+}
+
+fn apply_time_commands(
+    mut commands: EventReader<PlayerCommand>,
+    mut next_speed: ResMut<NextState<TimeSpeed>>,
+    mut next_time_state: ResMut<NextState<TimeState>>,
+    mut game_timer: ResMut<GameTimer>,
+) {
+    for command in commands.read() {
+        match command {
+            PlayerCommand::Pause => next_time_state.set(TimeState::Paused),
+            PlayerCommand::SetSpeed(speed) => {
+                next_time_state.set(TimeState::Running);
+                next_speed.set(*speed);
+                *game_timer = GameTimer::new(*speed);
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,15 +1,36 @@
 use crate::{
-    building::components::{Building, BuildingType},
+    building::{
+        components::{
+            AnchorTile, Building, BuildingTemplateMarker, BuildingType, CanBuild, Orientation,
+        },
+        desirability,
+        farming::Field,
+        housing::Household,
+        overlay::Overlay,
+        prices::{MarketPrices, HISTORY_MONTHS},
+        risk::{OnFire, Risk, Rubble},
+        site::{Finished, UnderConstruction},
+        storage::Storage,
+        trade::{TradePartner, TradeRoute, TradeSetting, TradeSettings},
+        upkeep::Condition,
+        water::{WaterConnected, WaterCoverage},
+        Placement,
+    },
+    command::PlayerCommand,
     cursor::SelectedTile,
-    grid::Occupied,
-    resources::GlobalResources,
-    time::{GameTimer, TimeSpeed, TimeState},
+    grid::{CurrentLevel, Level, Occupied, Terrain},
+    resources::{GlobalResources, Good, Ledger, MAX_TAX_RATE},
+    selection::SelectedArea,
+    time::{Calendar, TimeSpeed, TimeState},
 };
 use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_ecs_tilemap::tiles::{TilePos, TileStorage};
 use bevy_egui::{
     egui::{self, RichText},
     EguiContexts, EguiPlugin,
 };
+use std::collections::BTreeMap;
 
 pub struct UiPlugin;
 impl Plugin for UiPlugin {
@@ -17,14 +38,58 @@ impl Plugin for UiPlugin {
         app.add_plugins(EguiPlugin);
         app.add_systems(Update, ui_generic_resources);
         app.add_systems(Update, ui_time_controls);
-        app.add_systems(Update, ui_building_tooltip);
+        app.add_systems(Update, ui_building_info);
+        app.add_systems(Update, ui_construction_preview);
+        app.add_systems(Update, ui_overlays);
+        app.add_systems(Update, ui_trade);
+        app.init_resource::<AreaDesirability>();
+        app.add_systems(
+            Update,
+            (estimate_area_desirability, ui_area_inspector).chain(),
+        );
+        app.add_systems(Update, ui_tile_tooltip);
     }
 }
 
-fn ui_generic_resources(mut contexts: EguiContexts, resources: ResMut<GlobalResources>) {
+fn ui_generic_resources(
+    mut contexts: EguiContexts,
+    resources: Res<GlobalResources>,
+    mut player_commands: EventWriter<PlayerCommand>,
+    household_q: Query<&Household>,
+    ledger: Res<Ledger>,
+    calendar: Res<Calendar>,
+) {
     egui::Window::new("Resources").show(contexts.ctx_mut(), |ui| {
         ui.label("Gold".to_string());
         ui.label(RichText::new(resources.gold.to_string()).color(Color32::WHITE));
+        ui.label("Residents".to_string());
+        let residents: u32 = household_q
+            .iter()
+            .map(|household| household.residents)
+            .sum();
+        ui.label(RichText::new(residents.to_string()).color(Color32::WHITE));
+        ui.label("Tax rate".to_string());
+        ui.horizontal(|ui| {
+            if ui.button("-").clicked() && resources.tax_rate > 0 {
+                player_commands.send(PlayerCommand::SetTaxRate(resources.tax_rate - 1));
+            }
+            ui.label(RichText::new(format!("{}%", resources.tax_rate)).color(Color32::WHITE));
+            if ui.button("+").clicked() && resources.tax_rate < MAX_TAX_RATE {
+                player_commands.send(PlayerCommand::SetTaxRate(resources.tax_rate + 1));
+            }
+        });
+        for (good, amount) in resources.goods.iter() {
+            ui.label(good.name());
+            ui.label(RichText::new(amount.to_string()).color(Color32::WHITE));
+        }
+        if let Some(bookings) = ledger.months.get(&calendar.month) {
+            ui.label("This month".to_string());
+            for (entry, amount) in bookings {
+                ui.label(
+                    RichText::new(format!("{}: {:+}", entry.name(), amount)).color(Color32::WHITE),
+                );
+            }
+        }
     });
 }
 
@@ -42,74 +107,643 @@ fn ui_time_controls(
     mut contexts: EguiContexts,
     speed: Res<State<TimeSpeed>>,
     time_state: Res<State<TimeState>>,
-    mut next_speed: ResMut<NextState<TimeSpeed>>,
-    mut next_time_state: ResMut<NextState<TimeState>>,
-    mut game_timer: ResMut<GameTimer>,
+    calendar: Res<Calendar>,
+    mut player_commands: EventWriter<PlayerCommand>,
 ) {
     egui::Window::new("Time").show(contexts.ctx_mut(), |ui| {
+        ui.label(
+            RichText::new(format!(
+                "Month {}, {}",
+                calendar.month + 1,
+                calendar.season().name()
+            ))
+            .color(Color32::WHITE),
+        );
         if ui
             .button(RichText::new("Pause").color(is_enabled(time_state.get(), &TimeState::Paused)))
             .clicked()
         {
-            next_time_state.set(TimeState::Paused);
+            player_commands.send(PlayerCommand::Pause);
         }
         if ui
             .button(RichText::new("Normal").color(is_enabled(speed.get(), &TimeSpeed::Normal)))
             .clicked()
         {
-            next_time_state.set(TimeState::Running);
-            next_speed.set(TimeSpeed::Normal);
-            game_timer.0 = Timer::from_seconds(7.0, TimerMode::Repeating);
+            player_commands.send(PlayerCommand::SetSpeed(TimeSpeed::Normal));
         }
         if ui
             .button(RichText::new("Fast").color(is_enabled(speed.get(), &TimeSpeed::Fast)))
             .clicked()
         {
-            next_time_state.set(TimeState::Running);
-            next_speed.set(TimeSpeed::Fast);
-            game_timer.0 = Timer::from_seconds(3.5, TimerMode::Repeating);
+            player_commands.send(PlayerCommand::SetSpeed(TimeSpeed::Fast));
         }
         if ui
             .button(RichText::new("Faster").color(is_enabled(speed.get(), &TimeSpeed::Faster)))
             .clicked()
         {
-            next_time_state.set(TimeState::Running);
-            next_speed.set(TimeSpeed::Faster);
-            game_timer.0 = Timer::from_seconds(1.75, TimerMode::Repeating);
+            player_commands.send(PlayerCommand::SetSpeed(TimeSpeed::Faster));
         }
         if ui
             .button(RichText::new("Fastest").color(is_enabled(speed.get(), &TimeSpeed::Fastest)))
             .clicked()
         {
-            next_time_state.set(TimeState::Running);
-            next_speed.set(TimeSpeed::Fastest);
-            game_timer.0 = Timer::from_seconds(0.8525, TimerMode::Repeating);
+            player_commands.send(PlayerCommand::SetSpeed(TimeSpeed::Fastest));
         }
     });
 }
-fn ui_building_tooltip(
+/**
+* Shows the building clicked on for as long as it stays selected, so that its
+* buttons can be reached
+*/
+#[allow(clippy::type_complexity)]
+fn ui_building_info(
+    mut contexts: EguiContexts,
+    area: Res<SelectedArea>,
+    resources: Res<GlobalResources>,
+    mut player_commands: EventWriter<PlayerCommand>,
+    tile_storage_q: Query<&TileStorage>,
+    tiles_q: Query<&Terrain>,
+    buildings_q: Query<
+        (
+            &BuildingType,
+            &AnchorTile,
+            Option<&UnderConstruction>,
+            &Risk,
+            Option<&OnFire>,
+            &Condition,
+            Has<WaterConnected>,
+            Has<WaterCoverage>,
+            Option<&Field>,
+            &Storage,
+            Option<&Household>,
+        ),
+        With<Building>,
+    >,
+) {
+    if let Some(building_entity) = area.building {
+        if let Ok((
+            building,
+            AnchorTile(tile_pos),
+            site,
+            risk,
+            fire,
+            condition,
+            connected,
+            covered,
+            field,
+            storage,
+            household,
+        )) = buildings_q.get(building_entity)
+        {
+            // What the building stands on, going by the tile it's anchored to
+            let Some(terrain) = tile_storage_q
+                .iter()
+                .find_map(|storage| storage.get(tile_pos))
+                .and_then(|tile| tiles_q.get(tile).ok())
+            else {
+                return;
+            };
+            egui::Window::new("Building Info")
+                .collapsible(false)
+                .show(contexts.ctx_mut(), |ui| {
+                    ui.label(RichText::new(building.name()));
+                    ui.label(RichText::new("Lorem ipsum dolor sit amet..."));
+                    if let Some(site) = site {
+                        ui.label(RichText::new("Construction").color(Color32::WHITE));
+                        ui.label(RichText::new(format!("{:.0}%", site.progress * 100.)));
+                        for (good, needed) in building.materials() {
+                            let delivered = site.delivered.get(good).unwrap_or(&0);
+                            ui.label(RichText::new(format!(
+                                "{}: {}/{}",
+                                good.name(),
+                                delivered,
+                                needed
+                            )));
+                        }
+                    }
+                    if let Some(field) = field {
+                        ui.label(RichText::new("Crops").color(Color32::WHITE));
+                        ui.label(RichText::new(format!("{:.0}%", field.growth * 100.)));
+                        ui.label(RichText::new("Fertility here").color(Color32::WHITE));
+                        ui.label(RichText::new(format!("{:.0}%", terrain.fertility * 100.)));
+                    }
+                    if building.storage_capacity() > 0 {
+                        ui.label(RichText::new("Stored").color(Color32::WHITE));
+                        ui.label(RichText::new(format!(
+                            "{}/{}",
+                            storage.total(),
+                            building.storage_capacity()
+                        )));
+                        for (good, amount) in storage.goods.iter() {
+                            ui.label(RichText::new(format!("{}: {}", good.name(), amount)));
+                        }
+                    }
+                    if let Some(household) = household {
+                        ui.label(RichText::new("Residents").color(Color32::WHITE));
+                        ui.label(RichText::new(format!(
+                            "{}/{} ({})",
+                            household.residents,
+                            household.level.capacity(),
+                            household.level.name()
+                        )));
+                        ui.label(RichText::new("Food variety").color(Color32::WHITE));
+                        ui.label(RichText::new(format!(
+                            "{}/{}",
+                            household.variety,
+                            Good::FOOD.len()
+                        )));
+                        if household.hungry_months > 0 {
+                            ui.label(
+                                RichText::new(format!(
+                                    "Hungry for {} months",
+                                    household.hungry_months
+                                ))
+                                .color(Color32::RED),
+                            );
+                        }
+                    }
+                    if building.carries_water() || *building == BuildingType::House {
+                        let watered = connected || covered;
+                        ui.label(RichText::new("Water").color(Color32::WHITE));
+                        ui.label(RichText::new(if watered { "Yes" } else { "No" }));
+                    }
+                    if fire.is_some() {
+                        ui.label(RichText::new("On fire!").color(Color32::RED));
+                    }
+                    ui.label(RichText::new("Fire risk").color(Color32::WHITE));
+                    ui.label(RichText::new(format!("{:.0}%", risk.fire * 100.)));
+                    ui.label(RichText::new("Damage").color(Color32::WHITE));
+                    ui.label(RichText::new(format!("{:.0}%", risk.damage * 100.)));
+                    ui.label(RichText::new("Occupation").color(Color32::WHITE));
+                    ui.label(RichText::new(format!("max/{}", building.occupation())));
+                    ui.label(RichText::new("Production").color(Color32::WHITE));
+                    ui.label(RichText::new(format!(
+                        "{:.0}/100%",
+                        condition.efficiency() * 100.
+                    )));
+                    ui.label(RichText::new("Condition").color(Color32::WHITE));
+                    ui.label(RichText::new(format!("{:.0}%", condition.0 * 100.)));
+                    ui.label(RichText::new("Upkeep").color(Color32::WHITE));
+                    ui.label(RichText::new(format!("{}/month", building.upkeep())));
+                    let repair_cost = condition.repair_cost(*building);
+                    let can_repair =
+                        site.is_none() && repair_cost > 0 && resources.gold >= repair_cost as i32;
+                    if ui
+                        .add_enabled(
+                            can_repair,
+                            egui::Button::new(format!("Repair ({} gold)", repair_cost)),
+                        )
+                        .clicked()
+                    {
+                        player_commands.send(PlayerCommand::Repair {
+                            x: tile_pos.x,
+                            y: tile_pos.y,
+                        });
+                    }
+                    if let Some(upgrade) = building.upgrade() {
+                        let upgrade_cost = upgrade.cost() - building.cost();
+                        let can_upgrade = site.is_none() && resources.gold >= upgrade_cost as i32;
+                        if ui
+                            .add_enabled(
+                                can_upgrade,
+                                egui::Button::new(format!(
+                                    "Upgrade to {} ({} gold)",
+                                    upgrade.name(),
+                                    upgrade_cost
+                                )),
+                            )
+                            .clicked()
+                        {
+                            player_commands.send(PlayerCommand::Upgrade {
+                                x: tile_pos.x,
+                                y: tile_pos.y,
+                            });
+                        }
+                    }
+                });
+        }
+    }
+}
+
+/// Buildings as the area inspector and the tile tooltip look at them
+type InspectedBuildings<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static BuildingType,
+        &'static AnchorTile,
+        &'static Orientation,
+        Has<UnderConstruction>,
+        Option<&'static Household>,
+    ),
+    With<Building>,
+>;
+
+/**
+* Where every finished building stands, for working out desirability
+*/
+fn finished_placements(buildings_q: &InspectedBuildings) -> Vec<Placement> {
+    buildings_q
+        .iter()
+        .filter(|(_, _, _, site, _)| !site)
+        .map(|(building_type, anchor, orientation, _, _)| Placement {
+            building_type: *building_type,
+            anchor: anchor.0,
+            orientation: *orientation,
+        })
+        .collect()
+}
+
+/**
+* Tells what the tile under the cursor is like, next to the pointer
+*/
+fn ui_tile_tooltip(
     mut contexts: EguiContexts,
     selected_tile: Res<SelectedTile>,
-    tiles_q: Query<&Occupied>,
-    buildings_q: Query<&BuildingType, With<Building>>,
+    tiles_q: Query<(&TilePos, &Terrain, &Occupied)>,
+    buildings_q: InspectedBuildings,
+    rubble_q: Query<(), With<Rubble>>,
+) {
+    let ctx = contexts.ctx_mut();
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+    let Some((pos, terrain, occupied)) = selected_tile.0.and_then(|tile| tiles_q.get(tile).ok())
+    else {
+        return;
+    };
+
+    let occupant = occupied.0.and_then(|entity| {
+        if let Ok((building_type, ..)) = buildings_q.get(entity) {
+            Some(building_type.name())
+        } else {
+            rubble_q.contains(entity).then(|| "Rubble".to_string())
+        }
+    });
+    let desirability = desirability::at(*pos, &finished_placements(&buildings_q));
+
+    egui::show_tooltip_at_pointer(
+        ctx,
+        egui::LayerId::background(),
+        egui::Id::new("tile_tooltip"),
+        |ui| {
+            ui.label(RichText::new(terrain.terrain_type.name()).color(Color32::WHITE));
+            if terrain.is_buildable && occupied.0.is_none() {
+                ui.label(RichText::new("Buildable").color(Color32::GREEN));
+            } else {
+                ui.label(RichText::new("Not buildable").color(Color32::RED));
+            }
+            ui.label(format!("Fertility: {:.0}%", terrain.fertility * 100.));
+            ui.label(format!("Desirability (estimate): {:+.1}", desirability));
+            if let Some(occupant) = occupant {
+                ui.label(occupant);
+            }
+        },
+    );
+}
+
+/// Average desirability of the selected tiles, an estimate for the player
+/// that nothing in the simulation goes by
+#[derive(Resource, Default)]
+struct AreaDesirability {
+    /// The selection it was worked out for
+    corners: Option<(TilePos, TilePos)>,
+    average: f32,
+}
+
+/**
+* Works out the desirability of the selected tiles again, but only when the
+* selection changed or a building went up, came down or was upgraded
+*/
+fn estimate_area_desirability(
+    area: Res<SelectedArea>,
+    buildings_q: InspectedBuildings,
+    changed_q: Query<(), (With<Building>, Changed<BuildingType>)>,
+    mut removed: RemovedComponents<Building>,
+    mut finished: RemovedComponents<UnderConstruction>,
+    mut estimate: ResMut<AreaDesirability>,
+) {
+    let removed = removed.read().count() > 0;
+    let finished = finished.read().count() > 0;
+    let built = !changed_q.is_empty();
+    if area.corners == estimate.corners && !(removed || finished || built) {
+        return;
+    }
+
+    let tiles = area.tiles();
+    // Every finished building around counts, not just those inside
+    let placements = finished_placements(&buildings_q);
+    estimate.corners = area.corners;
+    estimate.average = tiles
+        .iter()
+        .map(|pos| desirability::at(*pos, &placements))
+        .sum::<f32>()
+        / tiles.len().max(1) as f32;
+}
+
+/**
+* Sums up the selected tiles: their terrain, the buildings standing on them,
+* how many people live there, how desirable they are and what they cost to keep
+*/
+fn ui_area_inspector(
+    mut contexts: EguiContexts,
+    area: Res<SelectedArea>,
+    tilemap_q: Query<&TileStorage>,
+    tiles_q: Query<(&Terrain, &Occupied)>,
+    buildings_q: InspectedBuildings,
+    rubble_q: Query<(), With<Rubble>>,
+    desirability: Res<AreaDesirability>,
+) {
+    let tiles = area.tiles();
+    if tiles.is_empty() {
+        return;
+    }
+    let Ok(tile_storage) = tilemap_q.get_single() else {
+        return;
+    };
+
+    let mut terrain_counts: BTreeMap<&str, u32> = BTreeMap::new();
+    let mut rubble = 0;
+    let mut inside = HashSet::new();
+    for tile in tiles.iter().filter_map(|pos| tile_storage.checked_get(pos)) {
+        let Ok((terrain, occupied)) = tiles_q.get(tile) else {
+            continue;
+        };
+        *terrain_counts
+            .entry(terrain.terrain_type.name())
+            .or_insert(0) += 1;
+        match occupied.0 {
+            Some(entity) if buildings_q.contains(entity) => {
+                inside.insert(entity);
+            }
+            Some(entity) if rubble_q.contains(entity) => rubble += 1,
+            _ => {}
+        }
+    }
+
+    let mut building_counts: BTreeMap<String, u32> = BTreeMap::new();
+    let mut sites = 0;
+    let mut residents = 0;
+    let mut upkeep = 0;
+    for (building_type, _, _, site, household) in buildings_q.iter_many(&inside) {
+        *building_counts.entry(building_type.name()).or_insert(0) += 1;
+        residents += household.map_or(0, |household| household.residents);
+        if site {
+            sites += 1;
+        } else {
+            upkeep += building_type.upkeep();
+        }
+    }
+
+    egui::Window::new("Area Info").show(contexts.ctx_mut(), |ui| {
+        ui.label(RichText::new(format!("{} tiles", tiles.len())).color(Color32::WHITE));
+        for (terrain, count) in &terrain_counts {
+            ui.label(format!("{}: {}", terrain, count));
+        }
+        if rubble > 0 {
+            ui.label(format!("Rubble: {}", rubble));
+        }
+        ui.label(RichText::new("Buildings").color(Color32::WHITE));
+        if building_counts.is_empty() {
+            ui.label("None");
+        }
+        for (name, count) in &building_counts {
+            ui.label(format!("{}: {}", name, count));
+        }
+        if sites > 0 {
+            ui.label(format!("Under construction: {}", sites));
+        }
+        ui.label(RichText::new("Residents").color(Color32::WHITE));
+        ui.label(residents.to_string());
+        ui.label(RichText::new("Desirability (estimate)").color(Color32::WHITE));
+        ui.label(format!("{:+.1}", desirability.average));
+        ui.label(RichText::new("Upkeep").color(Color32::WHITE));
+        ui.label(format!("{}/month", upkeep));
+    });
+}
+
+fn ui_construction_preview(
+    mut contexts: EguiContexts,
+    marker_q: Query<(&BuildingType, &CanBuild), With<BuildingTemplateMarker>>,
+) {
+    let Some((building, _)) = marker_q.iter().next() else {
+        return;
+    };
+    let total = marker_q.iter().count();
+    let valid = marker_q.iter().filter(|(_, can_build)| can_build.0).count();
+    let cost = valid as u32 * building.cost();
+
+    egui::Window::new("Construction")
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(RichText::new(building.name()));
+            ui.label(RichText::new("Buildable").color(Color32::WHITE));
+            ui.label(RichText::new(format!("{}/{}", valid, total)));
+            ui.label(RichText::new("Total cost").color(Color32::WHITE));
+            ui.label(RichText::new(cost.to_string()));
+            ui.label(RichText::new(
+                "Drag to place several, hold Shift to keep building",
+            ));
+        });
+}
+
+fn ui_overlays(mut contexts: EguiContexts, mut overlay: ResMut<Overlay>) {
+    egui::Window::new("Overlays (O)").show(contexts.ctx_mut(), |ui| {
+        for option in Overlay::ALL {
+            ui.radio_value(overlay.as_mut(), option, option.name());
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ui_trade(
+    mut contexts: EguiContexts,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    calendar: Res<Calendar>,
+    settings: Res<TradeSettings>,
+    prices: Res<MarketPrices>,
+    building_q: Query<&BuildingType, Finished>,
+    mut player_commands: EventWriter<PlayerCommand>,
+    mut amounts: Local<BTreeMap<Good, u32>>,
 ) {
-    if let Some(tile) = selected_tile.0 {
-        if let Ok(occupying_element) = tiles_q.get(tile) {
-            occupying_element.0.map(|building_entity| {
-                if let Ok(building) = buildings_q.get(building_entity) {
-                    egui::Window::new("Building Info").collapsible(false).show(
-                        contexts.ctx_mut(),
-                        |ui| {
-                            ui.label(RichText::new(building.name()));
-                            ui.label(RichText::new("Lorem ipsum dolor sit amet..."));
-                            ui.label(RichText::new("Occupation").color(Color32::WHITE));
-                            ui.label(RichText::new(format!("max/{}", building.occupation())));
-                            ui.label(RichText::new("Production").color(Color32::WHITE));
-                            ui.label(RichText::new("__/100%"));
-                        },
-                    );
+    let Some(level) = levels.get(current_level.0.id()) else {
+        return;
+    };
+    if level.trade_partners.is_empty() {
+        return;
+    }
+
+    let reachable = |partner: &TradePartner| {
+        building_q
+            .iter()
+            .any(|building| *building == partner.route.building())
+    };
+    egui::Window::new("Trade").show(contexts.ctx_mut(), |ui| {
+        world_map(ui, &level.trade_partners, reachable);
+        for partner in &level.trade_partners {
+            let reachable = reachable(partner);
+            ui.label(RichText::new(&partner.name).color(Color32::WHITE));
+            if reachable {
+                ui.label(format!(
+                    "{} route, next visit in {} months",
+                    partner.route.name(),
+                    partner.next_visit(calendar.month)
+                ));
+            } else {
+                ui.label(
+                    RichText::new(format!("Needs a {}", partner.route.building().name()))
+                        .color(Color32::RED),
+                );
+            }
+            for (good, usual) in &partner.buys {
+                let price = prices.price(*good, *usual);
+                ui.label(format!("Buys {} at {}", good.name(), price));
+            }
+            for (good, usual) in &partner.sells {
+                let price = prices.price(*good, *usual);
+                ui.label(format!("Sells {} at {}", good.name(), price));
+            }
+        }
+
+        ui.separator();
+        let goods = level
+            .trade_partners
+            .iter()
+            .flat_map(|partner| partner.buys.keys().chain(partner.sells.keys()).copied());
+        let mut traded: Vec<Good> = goods.collect();
+        traded.sort();
+        traded.dedup();
+        price_chart(ui, &prices, &traded);
+        for good in traded {
+            let current = settings.0.get(&good).copied().unwrap_or_default();
+            let mut setting = current;
+            ui.horizontal(|ui| {
+                ui.label(good.name());
+                // Kept while trade in the good is off, for when it's turned back on
+                let amount = amounts.entry(good).or_default();
+                if let TradeSetting::Import(set) | TradeSetting::Export(set) = setting {
+                    *amount = set;
+                }
+                ui.radio_value(&mut setting, TradeSetting::None, "None");
+                ui.radio_value(&mut setting, TradeSetting::Import(*amount), "Import up to");
+                ui.radio_value(&mut setting, TradeSetting::Export(*amount), "Export over");
+                if ui.add(egui::DragValue::new(amount)).changed() {
+                    setting = match setting {
+                        TradeSetting::Import(_) => TradeSetting::Import(*amount),
+                        TradeSetting::Export(_) => TradeSetting::Export(*amount),
+                        TradeSetting::None => TradeSetting::None,
+                    };
                 }
             });
+            if setting != current {
+                player_commands.send(PlayerCommand::SetTrade { good, setting });
+            }
         }
+    });
+}
+
+/// Colour of the road or sea lane to a partner on the world map
+fn route_color(route: TradeRoute) -> Color32 {
+    match route {
+        TradeRoute::Land => Color32::from_rgb(160, 110, 60),
+        TradeRoute::Sea => Color32::from_rgb(120, 170, 230),
+    }
+}
+
+/**
+* Draws the city in the middle and its trade partners around it, with the
+* route to each. Partners the city can't trade with yet are greyed out
+*/
+fn world_map(
+    ui: &mut egui::Ui,
+    partners: &[TradePartner],
+    reachable: impl Fn(&TradePartner) -> bool,
+) {
+    ui.label(RichText::new("World").color(Color32::WHITE));
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(240., 160.), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0., Color32::from_rgb(40, 60, 40));
+    painter.rect_stroke(rect, 0., (1., Color32::DARK_GRAY));
+
+    // The farthest partner lands just inside the edge
+    let reach = partners
+        .iter()
+        .map(|partner| partner.position[0].abs().max(partner.position[1].abs()))
+        .fold(1., f32::max);
+    let scale = (rect.width().min(rect.height()) / 2. - 16.) / reach;
+    let city = rect.center();
+    for partner in partners {
+        let [east, north] = partner.position;
+        let at = city + egui::vec2(east, -north) * scale;
+        let color = if reachable(partner) {
+            route_color(partner.route)
+        } else {
+            Color32::GRAY
+        };
+        painter.line_segment([city, at], (1.5, color));
+        painter.circle_filled(at, 4., color);
+        painter.text(
+            at + egui::vec2(0., -6.),
+            egui::Align2::CENTER_BOTTOM,
+            &partner.name,
+            egui::FontId::proportional(11.),
+            Color32::WHITE,
+        );
     }
+    painter.circle_filled(city, 5., Color32::GOLD);
+}
+
+/// Colour of each good's line in the price chart
+fn good_color(good: Good) -> Color32 {
+    match good {
+        Good::Timber => Color32::from_rgb(160, 110, 60),
+        Good::Stone => Color32::GRAY,
+        Good::Wheat => Color32::GOLD,
+        Good::Fruit => Color32::from_rgb(220, 80, 80),
+        Good::Fish => Color32::LIGHT_BLUE,
+        Good::Meat => Color32::from_rgb(200, 120, 160),
+    }
+}
+
+/**
+* Draws how the prices of `goods` went over the last months, as a share of
+* their usual price, the middle line being the usual price
+*/
+fn price_chart(ui: &mut egui::Ui, prices: &MarketPrices, goods: &[Good]) {
+    ui.label(RichText::new("Prices").color(Color32::WHITE));
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(240., 100.), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(rect, 0., (1., Color32::DARK_GRAY));
+    painter.hline(rect.x_range(), rect.center().y, (1., Color32::DARK_GRAY));
+
+    // Twice the usual price at the top, nothing at the bottom
+    let point = |month: usize, factor: f32| {
+        egui::pos2(
+            rect.left() + rect.width() * month as f32 / (HISTORY_MONTHS - 1) as f32,
+            rect.bottom() - rect.height() * (factor / 2.).clamp(0., 1.),
+        )
+    };
+    for good in goods {
+        let line: Vec<egui::Pos2> = prices
+            .history
+            .iter()
+            .enumerate()
+            .map(|(month, factors)| point(month, factors.get(good).copied().unwrap_or(1.)))
+            .collect();
+        painter.add(egui::Shape::line(line, (1.5, good_color(*good))));
+    }
+
+    ui.horizontal_wrapped(|ui| {
+        for good in goods {
+            ui.label(
+                RichText::new(format!(
+                    "{} {:.0}%",
+                    good.name(),
+                    prices.factor(*good) * 100.
+                ))
+                .color(good_color(*good)),
+            );
+        }
+    });
 }
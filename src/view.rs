@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::building::components::{AnchorTile, BuildingTemplateMarker, BuildingType, Orientation};
+use crate::building::depth::{ZIndex, WALKER_LIFT};
+use crate::building::farming::CropSprite;
+use crate::building::site::UnderConstruction;
+use crate::building::trade::Trader;
+use crate::building::walker::Walker;
+
+pub struct ViewPlugin;
+impl Plugin for ViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ViewRotation>();
+        app.add_systems(Update, rotate_view);
+        app.add_systems(PreUpdate, restore_unrotated);
+        app.add_systems(
+            PostUpdate,
+            (project_view, sort_depth)
+                .chain()
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Quarter turns the map is seen rotated by, clockwise
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewRotation(pub u8);
+
+impl ViewRotation {
+    /**
+     * Linear map from world positions as the simulation lays them out to where
+     * they're drawn. A quarter turn of the isometric grid turns the screen a quarter
+     * the other way and stretches it, so the diamonds line up again.
+     */
+    pub fn matrix(&self) -> Mat2 {
+        let quarter = Mat2::from_cols(Vec2::new(0., -0.5), Vec2::new(2., 0.));
+        (0..self.0).fold(Mat2::IDENTITY, |matrix, _| quarter * matrix)
+    }
+
+    pub fn project(&self, point: Vec2) -> Vec2 {
+        self.matrix() * point
+    }
+
+    pub fn unproject(&self, point: Vec2) -> Vec2 {
+        self.matrix().inverse() * point
+    }
+
+    /// Rotation and scale drawing the tilemap turned by `matrix`
+    fn rotation_and_scale(&self) -> (Quat, Vec3) {
+        let rotation = Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2 * self.0 as f32);
+        let scale = if self.0 % 2 == 1 {
+            Vec3::new(0.5, 2., 1.)
+        } else {
+            Vec3::ONE
+        };
+        (rotation, scale)
+    }
+
+    /// Which way a building facing `orientation` is seen facing
+    pub fn orient(&self, orientation: Orientation) -> Orientation {
+        // Turning the view one way turns what stands on the map the other way
+        (0..(4 - self.0 % 4) % 4).fold(orientation, |orientation, _| orientation.rotated())
+    }
+}
+
+/// The transform the simulation gave an entity, swapped in again before
+/// every update while the drawn one is in place
+#[derive(Component)]
+pub struct Unrotated(pub Transform);
+
+/**
+* Turns the view with Q and E, keeping the camera over the same spot
+*/
+fn rotate_view(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut view: ResMut<ViewRotation>,
+    mut camera_q: Query<&mut Transform, With<Camera>>,
+) {
+    let turns = if keys.just_pressed(KeyCode::KeyE) {
+        1
+    } else if keys.just_pressed(KeyCode::KeyQ) {
+        3
+    } else {
+        return;
+    };
+
+    let rotated = ViewRotation((view.0 + turns) % 4);
+    for mut transform in &mut camera_q {
+        let spot = view.unproject(transform.translation.truncate());
+        let drawn = rotated.project(spot);
+        transform.translation.x = drawn.x;
+        transform.translation.y = drawn.y;
+    }
+    *view = rotated;
+}
+
+fn restore_unrotated(mut unrotated_q: Query<(&mut Transform, &Unrotated)>) {
+    for (mut transform, unrotated) in &mut unrotated_q {
+        *transform = unrotated.0;
+    }
+}
+
+/// Everything that stands somewhere on the map, see `footings`
+type FootingQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Option<&'static AnchorTile>,
+        Option<&'static BuildingType>,
+        Option<&'static Orientation>,
+        Option<&'static Walker>,
+        Option<&'static Trader>,
+        Option<&'static CropSprite>,
+    ),
+    Or<(
+        With<AnchorTile>,
+        With<Walker>,
+        With<Trader>,
+        With<CropSprite>,
+    )>,
+>;
+
+/**
+* Where on the map each entity stands as the simulation lays it out, given
+* the untouched transform of the tilemap: the middle of the footprint for
+* buildings and markers, the tile of anything else
+*/
+fn footings(
+    footing_q: &FootingQuery,
+    logical: &Transform,
+    grid_size: &TilemapGridSize,
+    map_type: &TilemapType,
+) -> HashMap<Entity, Vec2> {
+    let ground = |pos: TilePos| {
+        logical
+            .transform_point(pos.center_in_world(grid_size, map_type).extend(0.))
+            .truncate()
+    };
+    footing_q
+        .iter()
+        .filter_map(
+            |(entity, anchor, building_type, orientation, walker, trader, crop)| {
+                let point = match (anchor, building_type, orientation, walker, trader, crop) {
+                    (Some(anchor), Some(building_type), Some(orientation), ..) => {
+                        let (w, h) = building_type.oriented_size(*orientation);
+                        let far = TilePos {
+                            x: anchor.0.x + w - 1,
+                            y: anchor.0.y + h - 1,
+                        };
+                        (ground(anchor.0) + ground(far)) / 2.
+                    }
+                    (Some(anchor), ..) => ground(anchor.0),
+                    (_, _, _, Some(walker), ..) => ground(walker.position),
+                    (_, _, _, _, Some(trader), _) => ground(trader.tile),
+                    (.., Some(crop)) => ground(crop.tile),
+                    _ => return None,
+                };
+                Some((entity, point))
+            },
+        )
+        .collect()
+}
+
+/**
+* Moves the tilemap and everything standing on it to where the rotated view
+* draws them, turning or mirroring buildings to match the side they're seen from
+*/
+#[allow(clippy::type_complexity)]
+fn project_view(
+    view: Res<ViewRotation>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut tilemap_q: Query<
+        (
+            Entity,
+            &TilemapGridSize,
+            &TilemapType,
+            &mut Transform,
+            Has<Unrotated>,
+        ),
+        With<TileStorage>,
+    >,
+    mut sprite_q: Query<
+        (
+            Entity,
+            &mut Transform,
+            Option<&Parent>,
+            Option<&mut Sprite>,
+            Option<&mut Unrotated>,
+        ),
+        Without<TileStorage>,
+    >,
+    footing_q: FootingQuery,
+    mut texture_q: Query<&mut Handle<Image>, Without<UnderConstruction>>,
+) {
+    let Ok((tilemap, grid_size, map_type, mut map_transform, saved)) = tilemap_q.get_single_mut()
+    else {
+        return;
+    };
+    let logical = *map_transform;
+    if !saved {
+        commands.entity(tilemap).insert(Unrotated(logical));
+    }
+    let matrix = view.matrix();
+    let (rotation, scale) = view.rotation_and_scale();
+    map_transform.translation = view
+        .project(logical.translation.truncate())
+        .extend(logical.translation.z);
+    map_transform.rotation = rotation;
+    map_transform.scale = scale;
+
+    // Where on the map each entity stands, around which it turns with the view
+    let footing = footings(&footing_q, &logical, grid_size, map_type);
+
+    for (entity, mut transform, parent, sprite, unrotated) in &mut sprite_q {
+        let Some(point) = footing.get(&entity) else {
+            continue;
+        };
+        match unrotated {
+            Some(mut unrotated) => unrotated.0 = *transform,
+            None => {
+                commands.entity(entity).insert(Unrotated(*transform));
+            }
+        }
+
+        // Children move relative to their parent, which already turned around its own footing
+        let pivot = match parent.and_then(|parent| footing.get(&parent.get())) {
+            Some(parent_point) => *point - *parent_point,
+            None => *point,
+        };
+        let shift = matrix * pivot - pivot;
+        transform.translation += shift.extend(0.);
+
+        if let (Some(mut sprite), Ok((_, _, Some(building_type), Some(orientation), ..))) =
+            (sprite, footing_q.get(entity))
+        {
+            let (path, flip_x) = building_type.oriented_sprite(view.orient(*orientation));
+            sprite.flip_x = flip_x;
+            // Sites keep their scaffolding whichever way they're seen
+            if let Ok(mut texture) = texture_q.get_mut(entity) {
+                if building_type.is_directional() {
+                    let drawn = asset_server.load(path);
+                    if *texture != drawn {
+                        *texture = drawn;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+* Draws whatever stands lower on the screen in front, walkers in front of a
+* building they share a tile with, crops flat under it all and markers over it
+*/
+#[allow(clippy::type_complexity)]
+fn sort_depth(
+    view: Res<ViewRotation>,
+    tilemap_q: Query<(&TilemapGridSize, &TilemapType, &Unrotated), With<TileStorage>>,
+    footing_q: FootingQuery,
+    layer_q: Query<(
+        Has<BuildingTemplateMarker>,
+        Has<Walker>,
+        Has<Trader>,
+        Has<CropSprite>,
+    )>,
+    mut sprite_q: Query<(Entity, &mut Transform, Option<&Parent>), Without<TileStorage>>,
+) {
+    let Ok((grid_size, map_type, logical)) = tilemap_q.get_single() else {
+        return;
+    };
+
+    let depth: HashMap<Entity, f32> = footings(&footing_q, &logical.0, grid_size, map_type)
+        .into_iter()
+        .map(|(entity, point)| {
+            let drawn = view.project(point);
+            let depth = match layer_q.get(entity) {
+                Ok((true, ..)) => ZIndex::BuildingMarker.depth(drawn),
+                Ok((_, walker, trader, _)) if walker || trader => {
+                    ZIndex::Building.depth(drawn) + WALKER_LIFT
+                }
+                Ok((.., true)) => ZIndex::Ground.depth(drawn),
+                _ => ZIndex::Building.depth(drawn),
+            };
+            (entity, depth)
+        })
+        .collect();
+
+    for (entity, mut transform, parent) in &mut sprite_q {
+        let Some(z) = depth.get(&entity) else {
+            continue;
+        };
+        // Children are drawn relative to their parent
+        let parent_z = parent
+            .and_then(|parent| depth.get(&parent.get()))
+            .copied()
+            .unwrap_or(0.);
+        transform.translation.z = z - parent_z;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::footprint::CellRule;
+    use crate::grid::MapLayout;
+
+    /// Where the cells of `building` facing `orientation` are drawn in `view`,
+    /// from the leftmost and lowest of them, and which need water
+    fn drawn_cells(
+        view: ViewRotation,
+        building: BuildingType,
+        orientation: Orientation,
+    ) -> Vec<(i32, i32, bool)> {
+        let layout = MapLayout::new(TilemapSize { x: 12, y: 12 });
+        let points: Vec<(Vec2, CellRule)> = building
+            .footprint(orientation)
+            .cells
+            .iter()
+            .map(|&(x, y, rule)| {
+                let point = Vec2::new(x as f32 + 4., y as f32 + 4.);
+                (view.project(layout.point_to_world(point)), rule)
+            })
+            .collect();
+        let low = points
+            .iter()
+            .fold(Vec2::MAX, |low, (point, _)| low.min(*point));
+        let mut cells: Vec<_> = points
+            .into_iter()
+            .map(|(point, rule)| {
+                let offset = (point - low).round();
+                (offset.x as i32, offset.y as i32, rule == CellRule::Water)
+            })
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn turned_buildings_look_like_the_way_they_are_seen_facing() {
+        for building in BuildingType::ALL {
+            for orientation in Orientation::ALL {
+                for turns in 0..4 {
+                    let view = ViewRotation(turns);
+                    assert_eq!(
+                        drawn_cells(view, building, orientation),
+                        drawn_cells(ViewRotation(0), building, view.orient(orientation)),
+                        "{building:?} facing {orientation:?}, view turned {turns} times"
+                    );
+                }
+            }
+        }
+    }
+}